@@ -0,0 +1,24 @@
+#!/bin/sh
+# run wayland tests against headless sway, e.g. on ci without display
+set -eu
+
+dir=$(mktemp -d)
+export XDG_RUNTIME_DIR="$dir"
+export WLR_BACKENDS=headless
+export WLR_LIBINPUT_NO_DEVICES=1
+export WLR_RENDERER=pixman
+
+sway --config /dev/null &
+sway=$!
+trap 'kill $sway; rm -rf "$dir"' EXIT
+
+# wait for the compositor socket
+for _ in $(seq 50); do
+    socket=$(ls "$dir" | grep '^wayland-[0-9]*$' | head -n 1 || true)
+    [ -n "$socket" ] && break
+    sleep 0.1
+done
+[ -n "$socket" ] || { echo "sway did not start" >&2; exit 1; }
+
+cd "$(dirname "$0")/.."
+WAYLAND_DISPLAY="$socket" cargo test --lib platform::wayland -- --include-ignored
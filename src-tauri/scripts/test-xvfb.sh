@@ -0,0 +1,30 @@
+#!/bin/sh
+# run x11 tests against xvfb, e.g. on ci without display
+# a window manager is needed for _NET_ACTIVE_WINDOW, openbox by default
+set -eu
+
+wm=${WM:-openbox}
+display=${DISPLAY_NUMBER:-:99}
+
+Xvfb "$display" -screen 0 1280x720x24 -nolisten tcp &
+xvfb=$!
+trap 'kill $xvfb ${wm_pid:-} 2>/dev/null || true' EXIT
+
+# wait for the server to accept connections
+for _ in $(seq 50); do
+    [ -e "/tmp/.X11-unix/X${display#:}" ] && break
+    sleep 0.1
+done
+[ -e "/tmp/.X11-unix/X${display#:}" ] || { echo "xvfb did not start" >&2; exit 1; }
+
+DISPLAY="$display" "$wm" &
+wm_pid=$!
+
+# wait for the window manager to announce ewmh support
+for _ in $(seq 50); do
+    DISPLAY="$display" xprop -root _NET_SUPPORTING_WM_CHECK 2>/dev/null | grep -q window && break
+    sleep 0.1
+done
+
+cd "$(dirname "$0")/.."
+DISPLAY="$display" cargo test --lib platform::x11 -- --include-ignored --test-threads 1
@@ -5,19 +5,275 @@ use crate::*;
 pub const MODE_TRANSLATION: usize = 0;
 pub const MODE_POLISHING: usize = 1;
 pub const MODE_COMPLETION: usize = 2;
+pub const MODE_EXPLANATION: usize = 3;
+const MODES: [usize; 4] = [
+    MODE_TRANSLATION,
+    MODE_POLISHING,
+    MODE_COMPLETION,
+    MODE_EXPLANATION,
+];
 
 pub const LLM_CHATGPT: usize = 0;
 pub const LLM_GROK: usize = 1;
+const LLMS: [usize; 2] = [LLM_CHATGPT, LLM_GROK];
+
+pub const INSERT_PASTE: usize = 0;
+pub const INSERT_TYPE: usize = 1;
+pub const INSERT_PASTE_CTRL_SHIFT_V: usize = 2;
+pub const INSERT_PASTE_SHIFT_INSERT: usize = 3;
+pub const INSERT_CLIPBOARD: usize = 5;
+const INSERT_METHODS: [usize; 5] = [
+    INSERT_PASTE,
+    INSERT_TYPE,
+    INSERT_PASTE_CTRL_SHIFT_V,
+    INSERT_PASTE_SHIFT_INSERT,
+    INSERT_CLIPBOARD,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Query {
+    pub input: String,
+    pub mode: usize,
+    pub llm: usize,
+    pub language: String,
+    pub style: Option<String>,
+    pub turns: Vec<Turn>,
+    // preceding text given for reference, not transformed
+    pub context: String,
+    // retry with a stricter prompt about the output language
+    pub strict: bool,
+}
 
-pub type Query = (String, usize);
+// previous output and follow-up instruction in refinement
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Turn {
+    pub output: String,
+    pub instruction: String,
+}
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub llm: usize,
+    // token per provider, a profile may select another provider than llm
+    pub chatgpt_token: String,
+    pub grok_token: String,
+    // single token of old configs, moved to the token of llm on load
+    #[serde(skip_serializing)]
     pub token: String,
     pub rate: f64,
     pub language: String,
     pub cache_size: usize,
+    pub profiles: Vec<Profile>,
+    pub shortcut: String,
+    pub mode_shortcuts: Vec<ModeShortcut>,
+    pub transform_shortcuts: Vec<ModeShortcut>,
+    pub prefill_selection: bool,
+    pub restore_clipboard: bool,
+    pub restore_clipboard_delay: f64,
+    pub insert_method: usize,
+    pub type_interval: f64,
+    pub window_width: f64,
+    pub window_height: f64,
+    pub history_enabled: bool,
+    pub history_size: usize,
+    pub history_days: u64,
+    pub undo_shortcut: String,
+    pub undo_with_backspace: bool,
+    pub undo_restore_input: bool,
+    pub candidates: usize,
+    pub chunk_size: usize,
+    pub chunk_concurrency: usize,
+    pub incremental: bool,
+    pub secondary_language: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            llm: LLM_CHATGPT,
+            chatgpt_token: Default::default(),
+            grok_token: Default::default(),
+            token: Default::default(),
+            rate: 0.5,
+            language: "English".into(),
+            cache_size: 1024,
+            profiles: Default::default(),
+            shortcut: "Ctrl+Space".into(),
+            mode_shortcuts: Default::default(),
+            transform_shortcuts: Default::default(),
+            prefill_selection: false,
+            restore_clipboard: true,
+            restore_clipboard_delay: 0.5,
+            insert_method: INSERT_PASTE,
+            type_interval: 0.01,
+            window_width: 512.0,
+            window_height: 512.0,
+            history_enabled: true,
+            history_size: 1000,
+            history_days: 90,
+            undo_shortcut: Default::default(),
+            undo_with_backspace: false,
+            undo_restore_input: false,
+            candidates: 1,
+            chunk_size: 2000,
+            chunk_concurrency: 4,
+            incremental: false,
+            secondary_language: Default::default(),
+        }
+    }
+}
+
+impl Config {
+    // never send the token of one provider to another
+    pub fn llm_token(&self, llm: usize) -> anyhow::Result<&str> {
+        let token = match llm {
+            LLM_CHATGPT => &self.chatgpt_token,
+            LLM_GROK => &self.grok_token,
+            _ => return Err(anyhow::anyhow!("llm {} is invalid", llm)),
+        };
+        if token.trim().is_empty() {
+            return Err(anyhow::anyhow!("api token of llm {} is not set", llm));
+        }
+        Ok(token)
+    }
+
+    // profiles are typed as json, so values out of the constants are possible
+    fn validate(&self) -> anyhow::Result<()> {
+        if !LLMS.contains(&self.llm) {
+            return Err(anyhow::anyhow!("llm {} is invalid", self.llm));
+        }
+        if !INSERT_METHODS.contains(&self.insert_method) {
+            return Err(anyhow::anyhow!(
+                "insert method {} is invalid",
+                self.insert_method
+            ));
+        }
+        for mode_shortcut in self.mode_shortcuts.iter().chain(&self.transform_shortcuts) {
+            if !MODES.contains(&mode_shortcut.mode) {
+                return Err(anyhow::anyhow!(
+                    "mode {} of shortcut {} is invalid",
+                    mode_shortcut.mode,
+                    mode_shortcut.shortcut
+                ));
+            }
+        }
+        for profile in &self.profiles {
+            profile
+                .validate()
+                .with_context(|| format!("profile {} is invalid", profile.app))?;
+        }
+        Ok(())
+    }
+
+    fn migrate_token(&mut self) {
+        let token = std::mem::take(&mut self.token);
+        if token.is_empty() {
+            return;
+        }
+        match self.llm {
+            LLM_CHATGPT if self.chatgpt_token.is_empty() => self.chatgpt_token = token,
+            LLM_GROK if self.grok_token.is_empty() => self.grok_token = token,
+            _ => {}
+        }
+    }
+}
+
+// per-application overrides, matched against process name or window class
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub app: String,
+    pub mode: Option<usize>,
+    pub language: Option<String>,
+    pub style: Option<String>,
+    pub llm: Option<usize>,
+    pub disabled_modes: Vec<usize>,
+    pub insert_method: Option<usize>,
+}
+
+impl Profile {
+    fn validate(&self) -> anyhow::Result<()> {
+        for mode in self.mode.iter().chain(&self.disabled_modes) {
+            if !MODES.contains(mode) {
+                return Err(anyhow::anyhow!("mode {} is invalid", mode));
+            }
+        }
+        if let Some(llm) = self.llm.filter(|llm| !LLMS.contains(llm)) {
+            return Err(anyhow::anyhow!("llm {} is invalid", llm));
+        }
+        if let Some(method) = self
+            .insert_method
+            .filter(|method| !INSERT_METHODS.contains(method))
+        {
+            return Err(anyhow::anyhow!("insert method {} is invalid", method));
+        }
+        Ok(())
+    }
+
+    pub fn is_match(&self, info: &platform::AppInfo) -> bool {
+        let app = self.app.trim();
+        !app.is_empty()
+            && (app.eq_ignore_ascii_case(&info.process) || app.eq_ignore_ascii_case(&info.class))
+    }
+}
+
+// shortcut which runs the specific mode
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ModeShortcut {
+    pub shortcut: String,
+    pub mode: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShortcutAction {
+    Pop(Option<usize>),
+    Transform(usize),
+    Undo,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Session {
+    target: Option<platform::WindowId>,
+    app: Option<platform::AppInfo>,
+    profile: Option<Profile>,
+    mode: Option<usize>,
+    input: Option<String>,
+    // last query sent to llm, recorded in history on confirm
+    query: Option<Query>,
+}
+
+// last text inserted into the target, removed by undo shortcut
+#[derive(Debug, Clone)]
+struct Insertion {
+    target: Option<platform::WindowId>,
+    profile: Profile,
+    input: Option<String>,
+    length: usize,
+}
+
+// detected is the language of the input when identified
+// suspect output may not be in the language even after retry
+#[derive(Debug, Clone, serde::Serialize)]
+struct Output {
+    outputs: Vec<String>,
+    // hunks per candidate, sent together not to merge with stale candidates
+    diffs: Vec<Vec<diff::Hunk>>,
+    language: String,
+    detected: Option<String>,
+    suspect: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct ShowWindow {
+    mode: Option<usize>,
+    disabled_modes: Vec<usize>,
+    input: Option<String>,
+}
+
+struct AppPlatform {
+    platform: std::sync::Arc<dyn platform::Platform>,
 }
 
 struct AppConfig {
@@ -29,8 +285,26 @@ struct AppChannel {
     _th_input: tauri::async_runtime::JoinHandle<()>,
 }
 
+struct AppSession {
+    session: tauri::async_runtime::Mutex<Session>,
+}
+
+struct AppInsertion {
+    insertion: tauri::async_runtime::Mutex<Option<Insertion>>,
+}
+
+struct AppShortcut {
+    shortcuts:
+        tauri::async_runtime::Mutex<Vec<(tauri_plugin_global_shortcut::Shortcut, ShortcutAction)>>,
+    errors: tauri::async_runtime::Mutex<Vec<String>>,
+}
+
+struct AppHistory {
+    history: tauri::async_runtime::Mutex<history::History>,
+}
+
 struct AppCache {
-    cache: tauri::async_runtime::Mutex<lru::LruCache<Query, String>>,
+    cache: tauri::async_runtime::Mutex<lru::LruCache<Query, llm::Outputs>>,
 }
 
 pub fn setup_plugin_clipboard() -> anyhow::Result<impl tauri::plugin::Plugin<tauri::Wry>> {
@@ -39,17 +313,17 @@ pub fn setup_plugin_clipboard() -> anyhow::Result<impl tauri::plugin::Plugin<tau
     Ok(tauri_plugin_clipboard_manager::init())
 }
 
+pub fn setup_plugin_notification() -> anyhow::Result<impl tauri::plugin::Plugin<tauri::Wry>> {
+    log::info!("setup plugin notification");
+
+    Ok(tauri_plugin_notification::init())
+}
+
 pub fn setup_plugin_global_shortcut() -> anyhow::Result<impl tauri::plugin::Plugin<tauri::Wry>> {
     log::info!("setup plugin global_shortcut");
 
-    let key = tauri_plugin_global_shortcut::Shortcut::new(
-        Some(tauri_plugin_global_shortcut::Modifiers::CONTROL),
-        tauri_plugin_global_shortcut::Code::Space,
-    );
-
+    // shortcuts are registered from config in setup
     let plugin = tauri_plugin_global_shortcut::Builder::new()
-        .with_shortcut(key)
-        .unwrap()
         .with_handler(global_shortcut_handle)
         .build();
     Ok(plugin)
@@ -58,11 +332,25 @@ pub fn setup_plugin_global_shortcut() -> anyhow::Result<impl tauri::plugin::Plug
 // no occur panic in handle fn
 fn global_shortcut_handle(
     app: &tauri::AppHandle,
-    _: &tauri_plugin_global_shortcut::Shortcut,
+    shortcut: &tauri_plugin_global_shortcut::Shortcut,
     event: tauri_plugin_global_shortcut::ShortcutEvent,
 ) {
     if event.state() == tauri_plugin_global_shortcut::ShortcutState::Pressed {
-        match pop_window_main(app) {
+        let state = tauri::Manager::state::<AppShortcut>(app);
+        let action = state
+            .shortcuts
+            .blocking_lock()
+            .iter()
+            .find(|(other, _)| other.id() == shortcut.id())
+            .map(|(_, action)| *action);
+
+        let result = match action {
+            Some(ShortcutAction::Pop(mode)) => pop_window_main(app, mode),
+            Some(ShortcutAction::Transform(mode)) => transform_selection(app, mode),
+            Some(ShortcutAction::Undo) => undo_insertion(app),
+            None => Err(anyhow::anyhow!("shortcut {} is not bound", shortcut)),
+        };
+        match result {
             Ok(_) => {}
             Err(e) => {
                 log::error!("error occured {}", e);
@@ -72,61 +360,426 @@ fn global_shortcut_handle(
     }
 }
 
-fn pop_window_main(app: &tauri::AppHandle) -> anyhow::Result<()> {
+fn pop_window_main(app: &tauri::AppHandle, mode: Option<usize>) -> anyhow::Result<()> {
+    let platform = get_platform(app);
+
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let config = state.config.blocking_lock().clone();
+
+    let session = open_session(platform.as_ref(), &config, mode)?;
+    let window_id = session.target;
+
+    let state = tauri::Manager::state::<AppSession>(app);
+    *state.session.blocking_lock() = session;
+
+    locate_window_main(app, platform.as_ref(), window_id)?;
+
+    show_window_main(app)?;
+
+    Ok(())
+}
+
+// focus window, its profile and selection at the time window main pops up
+fn open_session(
+    platform: &dyn platform::Platform,
+    config: &Config,
+    mode: Option<usize>,
+) -> anyhow::Result<Session> {
     log::info!("get forground window center position");
 
-    let hwnd = unsafe { windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow() };
+    // foreground window is unavailable on wayland
+    let window_id = match platform.get_foreground_window() {
+        Ok(window_id) => Some(window_id),
+        Err(e) => {
+            log::error!("error occured {}", e);
+            None
+        }
+    };
 
     // check focus window
-    if let Some(window) = tauri::Manager::get_webview_window(app, "main") {
-        let slf_hwnd = window.hwnd()?;
-        if hwnd == slf_hwnd {
-            return Err(anyhow::anyhow!("focus window must be no window main"));
+    if let Some(window_id) = window_id {
+        if platform.is_own_window(window_id)? {
+            return Err(anyhow::anyhow!(
+                "focus window must be no window main or config"
+            ));
         }
     }
-    if let Some(window) = tauri::Manager::get_webview_window(app, "config") {
-        let slf_hwnd = window.hwnd()?;
-        if hwnd == slf_hwnd {
-            return Err(anyhow::anyhow!("focus window must be no window config"));
+
+    let info = get_foreground_app(platform);
+    let profile = info
+        .as_ref()
+        .and_then(|info| select_profile(&config.profiles, info));
+
+    let input = if config.prefill_selection {
+        match capture_selection(platform) {
+            Ok(input) => Some(input),
+            Err(e) => {
+                log::error!("error occured {}", e);
+                None
+            }
         }
-    }
+    } else {
+        None
+    };
+
+    Ok(Session {
+        target: window_id,
+        app: info,
+        profile,
+        mode,
+        input,
+        query: None,
+    })
+}
+
+// below the caret, or center of the focus window or screen, all in physical pixels
+fn locate_window_main(
+    app: &tauri::AppHandle,
+    platform: &dyn platform::Platform,
+    window_id: Option<platform::WindowId>,
+) -> anyhow::Result<()> {
+    log::info!("get caret or focus window rect");
+
+    let caret = match window_id.map(|window_id| platform.get_caret_rect(window_id)) {
+        Some(Ok(caret)) => Some(caret),
+        Some(Err(e)) => {
+            log::error!("error occured {}", e);
+            None
+        }
+        None => None,
+    };
+    let rect = match window_id {
+        Some(window_id) => platform.get_window_rect(window_id)?,
+        None => get_screen_rect(app)?,
+    };
+    let x = (rect.right + rect.left) / 2;
+    let y = (rect.top + rect.bottom) / 2;
+
+    // monitor which contains the anchor decides scale factor and bounds
+    let (anchor_x, anchor_y) = match caret {
+        Some(caret) => (caret.left, caret.bottom),
+        None => (x, y),
+    };
+    let monitor = match app.monitor_from_point(anchor_x as f64, anchor_y as f64)? {
+        Some(monitor) => monitor,
+        None => app.primary_monitor()?.context("monitor is not found")?,
+    };
+    let monitor_rect = platform::Rect {
+        left: monitor.position().x,
+        top: monitor.position().y,
+        right: monitor.position().x + monitor.size().width as i32,
+        bottom: monitor.position().y + monitor.size().height as i32,
+    };
+    let work_area = match platform.get_work_area(monitor_rect) {
+        Ok(work_area) => work_area,
+        Err(e) => {
+            log::error!("error occured {}", e);
+            monitor_rect
+        }
+    };
 
-    log::info!("get focus window center");
-    let mut lprect = windows::Win32::Foundation::RECT::default();
-    unsafe { windows::Win32::UI::WindowsAndMessaging::GetWindowRect(hwnd, &mut lprect) }
-        .context("failed to get window rect")?;
-    let x = (lprect.right + lprect.left) as f64 / 2.0;
-    let y = (lprect.top + lprect.bottom) as f64 / 2.0;
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let (width, height) = {
+        let config = state.config.blocking_lock();
+        (config.window_width, config.window_height)
+    };
+    let scale_factor = monitor.scale_factor();
+    let width = (width * scale_factor).round() as i32;
+    let height = (height * scale_factor).round() as i32;
+
+    // flip above the caret when there is no room below
+    let (left, top) = match caret {
+        Some(caret) if caret.bottom + height <= work_area.bottom => (caret.left, caret.bottom),
+        Some(caret) => (caret.left, caret.top - height),
+        None => (x - width / 2, y - height / 2),
+    };
+    let left = left.min(work_area.right - width).max(work_area.left);
+    let top = top.min(work_area.bottom - height).max(work_area.top);
 
-    const W_WIDTH: f64 = 512.0;
-    const W_HEIGHT: f64 = 512.0;
     let window =
         tauri::Manager::get_webview_window(app, "main").context("window main is not found")?;
 
     log::info!("locate window main");
-    let x = x - W_WIDTH / 2.0;
-    let y = y - W_HEIGHT / 2.0;
-    let position = tauri::Position::Logical(tauri::LogicalPosition::new(x, y));
+    let position = tauri::Position::Physical(tauri::PhysicalPosition::new(left, top));
     window.set_position(position)?;
 
     log::info!("resize window main");
-    let size = tauri::Size::Logical(tauri::LogicalSize::new(W_WIDTH, W_HEIGHT));
+    let size = tauri::Size::Physical(tauri::PhysicalSize::new(width as u32, height as u32));
     window.set_size(size)?;
 
-    show_window_main(app)?;
+    Ok(())
+}
+
+// monitor under the cursor, or primary monitor if cursor is also unavailable
+fn get_screen_rect(app: &tauri::AppHandle) -> anyhow::Result<platform::Rect> {
+    let monitor = match app.cursor_position() {
+        Ok(position) => app.monitor_from_point(position.x, position.y)?,
+        Err(_) => None,
+    };
+    let monitor = match monitor {
+        Some(monitor) => monitor,
+        None => app.primary_monitor()?.context("monitor is not found")?,
+    };
+
+    let position = monitor.position();
+    let size = monitor.size();
+    Ok(platform::Rect {
+        left: position.x,
+        top: position.y,
+        right: position.x + size.width as i32,
+        bottom: position.y + size.height as i32,
+    })
+}
+
+fn get_foreground_app(platform: &dyn platform::Platform) -> Option<platform::AppInfo> {
+    let info = match platform
+        .get_foreground_window()
+        .and_then(|window_id| platform.get_app_info(window_id))
+    {
+        Ok(info) => info,
+        Err(e) => {
+            log::error!("error occured {}", e);
+            return None;
+        }
+    };
+    log::info!("focus window app {:?}", info);
+    Some(info)
+}
+
+fn find_profile(app: &tauri::AppHandle, info: &platform::AppInfo) -> Option<Profile> {
+    log::info!("select profile of focus window");
+
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let profiles = state.config.blocking_lock().profiles.clone();
+    select_profile(&profiles, info)
+}
+
+// the first matching profile wins
+fn select_profile(profiles: &[Profile], info: &platform::AppInfo) -> Option<Profile> {
+    profiles
+        .iter()
+        .find(|profile| profile.is_match(info))
+        .cloned()
+}
+
+fn transform_selection(app: &tauri::AppHandle, mode: usize) -> anyhow::Result<()> {
+    log::info!("transform selection");
+
+    let profile = get_foreground_app(get_platform(app).as_ref())
+        .and_then(|info| find_profile(app, &info))
+        .unwrap_or_default();
+
+    // run without window main, report errors by notification
+    let app_clone = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match transform_selection_inner(&app_clone, profile, mode).await {
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("error occured {}", e);
+                notify(&app_clone, &format!("Failed to transform selection: {}", e));
+            }
+        }
+    });
+
+    Ok(())
+}
+
+async fn transform_selection_inner(
+    app: &tauri::AppHandle,
+    profile: Profile,
+    mode: usize,
+) -> anyhow::Result<()> {
+    let platform = get_platform(app);
+    let target = platform.get_foreground_window().ok();
+
+    // copy waits for the clipboard, not to block the async runtime
+    let platform_clone = platform.clone();
+    let input =
+        tauri::async_runtime::spawn_blocking(move || copy_clipboard(platform_clone.as_ref()))
+            .await??;
+
+    let cache = tauri::Manager::state::<AppCache>(app);
+
+    // clone not to hold the lock over requests, commands lock it on main thread
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let config = state.config.lock().await.clone();
+
+    let mut query = build_query(&config, &profile, input.trim().into(), mode)?;
+    apply_language_pair(&config, &mut query);
+    let outputs = llm::request_llm_chunked(&cache.cache, &query, &config).await?;
+    // inserted anyway, the user can undo it
+    if outputs.suspect {
+        log::info!("output may not be in {}", query.language);
+        notify(
+            app,
+            &format!("Output may not be in {}, check it.", query.language),
+        );
+    }
+    let (outputs, _) = split_explanation(&query, outputs.outputs)?;
+    let output = outputs.into_iter().next().unwrap_or_default();
+    if output.is_empty() {
+        return Err(anyhow::anyhow!("output is empty"));
+    }
+
+    // user may switch windows during the request
+    if let Some(target) = target {
+        let platform_clone = platform.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            restore_focus(platform_clone.as_ref(), target)
+        })
+        .await??;
+    }
+
+    // paste and typing block on key synthesis, e.g. portal thread on wayland
+    let platform_clone = platform.clone();
+    let (output_clone, config_clone, profile_clone) =
+        (output.clone(), config.clone(), profile.clone());
+    tauri::async_runtime::spawn_blocking(move || {
+        insert_text(
+            &platform_clone,
+            &output_clone,
+            &config_clone,
+            &profile_clone,
+        )
+    })
+    .await??;
+    notify_clipboard_only(app, &config, &profile);
+
+    let insertion = create_insertion(&config, target, profile, Some(input), &output);
+    let state = tauri::Manager::state::<AppInsertion>(app);
+    *state.insertion.lock().await = insertion;
+
+    Ok(())
+}
+
+// nothing is inserted into the target when copying to clipboard only
+fn create_insertion(
+    config: &Config,
+    target: Option<platform::WindowId>,
+    profile: Profile,
+    input: Option<String>,
+    output: &str,
+) -> Option<Insertion> {
+    if insert_method(config, &profile) == INSERT_CLIPBOARD {
+        return None;
+    }
+
+    // editors delete a grapheme per backspace, e.g. emoji or crlf
+    // typing skips control characters, which must not be counted
+    let output = match insert_method(config, &profile) {
+        INSERT_TYPE => output.chars().filter(|c| platform::is_typed(*c)).collect(),
+        _ => output.to_string(),
+    };
+    let length =
+        unicode_segmentation::UnicodeSegmentation::graphemes(output.as_str(), true).count();
+    Some(Insertion {
+        target,
+        profile,
+        input,
+        length,
+    })
+}
+
+fn undo_insertion(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    log::info!("undo insertion");
+
+    // backspaces and restored input are typed off the main thread
+    let app_clone = app.clone();
+    tauri::async_runtime::spawn_blocking(move || match undo_insertion_inner(&app_clone) {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("error occured {}", e);
+            notify(&app_clone, &format!("Failed to undo: {}", e));
+        }
+    });
+
+    Ok(())
+}
+
+fn undo_insertion_inner(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let state = tauri::Manager::state::<AppInsertion>(app);
+    let insertion = state
+        .insertion
+        .blocking_lock()
+        .take()
+        .context("nothing to undo")?;
+
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let config = state.config.blocking_lock().clone();
+
+    let platform = get_platform(app);
+    let profile = insertion.profile.clone();
+    revert_insertion(&platform, &config, insertion)?;
+    if config.undo_restore_input {
+        notify_clipboard_only(app, &config, &profile);
+    }
+
+    Ok(())
+}
+
+// keys would go to another window after focus changes, unknown target is refused
+fn revert_insertion(
+    platform: &std::sync::Arc<dyn platform::Platform>,
+    config: &Config,
+    insertion: Insertion,
+) -> anyhow::Result<()> {
+    let target = insertion
+        .target
+        .context("window of the insertion is unknown")?;
+    if platform.get_foreground_window().ok() != Some(target) {
+        return Err(anyhow::anyhow!(
+            "focus window has changed since the insertion"
+        ));
+    }
+
+    if config.undo_with_backspace {
+        platform.send_backspace(insertion.length)?;
+    } else {
+        platform.send_undo()?;
+    }
+
+    if config.undo_restore_input {
+        if let Some(input) = insertion.input {
+            insert_text(platform, &input, config, &insertion.profile)?;
+        }
+    }
 
     Ok(())
 }
 
+fn notify(app: &tauri::AppHandle, message: &str) {
+    let notification = tauri_plugin_notification::NotificationExt::notification(app);
+    match notification
+        .builder()
+        .title("Quicklime")
+        .body(message)
+        .show()
+    {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("error occured {}", e);
+        }
+    }
+}
+
 fn show_window_main(app: &tauri::AppHandle) -> anyhow::Result<()> {
     log::info!("show window main");
 
     let window =
         tauri::Manager::get_webview_window(app, "main").context("window main is not found")?;
     if !window.is_visible()? {
+        let state = tauri::Manager::state::<AppSession>(app);
+        let session = state.session.blocking_lock().clone();
+        let profile = session.profile.unwrap_or_default();
+        let payload = ShowWindow {
+            mode: session.mode.or(profile.mode),
+            disabled_modes: profile.disabled_modes,
+            input: session.input,
+        };
+
         window.show()?;
         window.set_focus()?;
-        tauri::Emitter::emit(&window, "show_window", ())?;
+        tauri::Emitter::emit(&window, "show_window", payload)?;
     }
 
     Ok(())
@@ -137,8 +790,14 @@ pub fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
 
     create_window_main(app.handle())?;
 
+    setup_platform(app.handle())?;
+
     setup_config(app.handle())?;
+    setup_session(app.handle())?;
+    setup_insertion(app.handle())?;
+    setup_shortcut(app.handle())?;
     setup_cache(app.handle())?;
+    setup_history(app.handle())?;
     setup_channel(app.handle())?;
     setup_tray(app.handle())?;
 
@@ -168,20 +827,28 @@ fn create_window_main(app: &tauri::AppHandle) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn setup_platform(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let platform = platform::create(app);
+    let state = AppPlatform { platform };
+    tauri::Manager::manage(app, state);
+
+    Ok(())
+}
+
+fn get_platform(app: &tauri::AppHandle) -> std::sync::Arc<dyn platform::Platform> {
+    let state = tauri::Manager::state::<AppPlatform>(app);
+    state.platform.clone()
+}
+
 fn setup_config(app: &tauri::AppHandle) -> anyhow::Result<()> {
     let filepath =
         tauri::Manager::path(app).resolve("config.json", tauri::path::BaseDirectory::AppConfig)?;
 
-    let config = std::fs::File::open(filepath)
+    let mut config = std::fs::File::open(filepath)
         .ok()
         .and_then(|rdr| serde_json::from_reader::<_, Config>(rdr).ok())
-        .unwrap_or_else(|| Config {
-            llm: LLM_CHATGPT,
-            token: Default::default(),
-            rate: 0.5,
-            language: "English".into(),
-            cache_size: 1024,
-        });
+        .unwrap_or_default();
+    config.migrate_token();
 
     let config = tauri::async_runtime::Mutex::new(config);
     let state = AppConfig { config };
@@ -190,56 +857,261 @@ fn setup_config(app: &tauri::AppHandle) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn setup_cache(app: &tauri::AppHandle) -> anyhow::Result<()> {
-    let state = tauri::Manager::state::<AppConfig>(app);
-    let config = state.config.blocking_lock();
+fn setup_session(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let session = tauri::async_runtime::Mutex::new(Session::default());
+    let state = AppSession { session };
+    tauri::Manager::manage(app, state);
 
-    let cache_size = std::num::NonZeroUsize::try_from(config.cache_size)?;
-    let cache = lru::LruCache::new(cache_size);
+    Ok(())
+}
 
-    let cache = tauri::async_runtime::Mutex::new(cache);
-    let state = AppCache { cache };
+fn setup_insertion(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let insertion = tauri::async_runtime::Mutex::new(None);
+    let state = AppInsertion { insertion };
     tauri::Manager::manage(app, state);
 
     Ok(())
 }
 
-fn setup_channel(app: &tauri::AppHandle) -> anyhow::Result<()> {
-    let state = tauri::Manager::state::<AppConfig>(app);
-    let config = state.config.blocking_lock();
+fn setup_shortcut(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let shortcuts = tauri::async_runtime::Mutex::new(Default::default());
+    let errors = tauri::async_runtime::Mutex::new(Default::default());
+    let state = AppShortcut { shortcuts, errors };
+    tauri::Manager::manage(app, state);
 
-    let min_duration = std::time::Duration::from_secs_f64(config.rate);
+    register_shortcuts(app)?;
 
-    let (tx_input, rx_input) = crossbeam_channel::unbounded::<Query>();
+    Ok(())
+}
 
-    let app_clone = app.clone();
-    let _th_input = tauri::async_runtime::spawn(async move {
-        loop {
-            let Ok((input, mode)) = rx_input.recv() else {
-                continue;
-            };
+// register shortcuts from config and keep errors for window config
+fn register_shortcuts(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let config = state.config.blocking_lock().clone();
 
-            if !rx_input.is_empty() {
-                continue;
+    let mut bindings = vec![(config.shortcut, ShortcutAction::Pop(None))];
+    for mode_shortcut in config.mode_shortcuts {
+        let action = ShortcutAction::Pop(Some(mode_shortcut.mode));
+        bindings.push((mode_shortcut.shortcut, action));
+    }
+    for mode_shortcut in config.transform_shortcuts {
+        let action = ShortcutAction::Transform(mode_shortcut.mode);
+        bindings.push((mode_shortcut.shortcut, action));
+    }
+    bindings.push((config.undo_shortcut, ShortcutAction::Undo));
+
+    let global_shortcut = tauri_plugin_global_shortcut::GlobalShortcutExt::global_shortcut(app);
+    let state = tauri::Manager::state::<AppShortcut>(app);
+    let previous = state.shortcuts.blocking_lock().clone();
+
+    let (shortcuts, released, errors) = update_shortcuts(&previous, bindings, |shortcut| {
+        global_shortcut.register(shortcut)?;
+        Ok(())
+    });
+    for shortcut in released {
+        log::info!("unregister shortcut {}", shortcut);
+        match global_shortcut.unregister(shortcut) {
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("error occured {}", e);
             }
+        }
+    }
 
-            log::info!("input: {}", input);
-            let instant = std::time::Instant::now();
+    for error in &errors {
+        log::error!("error occured {}", error);
+    }
 
-            let state = tauri::Manager::state::<AppCache>(&app_clone);
-            let mut cache = state.cache.lock().await;
+    *state.shortcuts.blocking_lock() = shortcuts;
+    *state.errors.blocking_lock() = errors;
 
-            let state = tauri::Manager::state::<AppConfig>(&app_clone);
-            let config = state.config.lock().await;
+    Ok(())
+}
 
-            let input = input.trim();
-            let output = match llm::request_llm(&mut cache, input, mode, &config).await {
-                Ok(output) => output,
+// new shortcuts are registered before the previous ones are released,
+// and an action whose new shortcut fails keeps the previous one
+fn update_shortcuts(
+    previous: &[(tauri_plugin_global_shortcut::Shortcut, ShortcutAction)],
+    bindings: Vec<(String, ShortcutAction)>,
+    mut register: impl FnMut(tauri_plugin_global_shortcut::Shortcut) -> anyhow::Result<()>,
+) -> (
+    Vec<(tauri_plugin_global_shortcut::Shortcut, ShortcutAction)>,
+    Vec<tauri_plugin_global_shortcut::Shortcut>,
+    Vec<String>,
+) {
+    let mut shortcuts = vec![];
+    let mut failures = vec![];
+    let mut errors = vec![];
+    for (text, action) in bindings {
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let shortcut = match text.parse::<tauri_plugin_global_shortcut::Shortcut>() {
+            Ok(shortcut) => shortcut,
+            Err(e) => {
+                errors.push(format!("invalid shortcut {}: {}", text, e));
+                failures.push(action);
+                continue;
+            }
+        };
+
+        if shortcuts.iter().any(|(other, _)| *other == shortcut) {
+            errors.push(format!("shortcut {} conflicts with another shortcut", text));
+            failures.push(action);
+            continue;
+        }
+
+        // already registered shortcuts are kept as they are
+        let registered = previous.iter().any(|(other, _)| *other == shortcut);
+
+        // fail when the shortcut is already taken by another application
+        if !registered {
+            if let Err(e) = register(shortcut) {
+                errors.push(format!("failed to register shortcut {}: {}", text, e));
+                failures.push(action);
+                continue;
+            }
+            log::info!("register shortcut {}", text);
+        }
+
+        shortcuts.push((shortcut, action));
+    }
+
+    let mut released = vec![];
+    for (shortcut, action) in previous {
+        if shortcuts.iter().any(|(other, _)| other == shortcut) {
+            continue;
+        }
+        if failures.contains(action) {
+            errors.push(format!("previous shortcut {} is kept", shortcut));
+            shortcuts.push((*shortcut, *action));
+            continue;
+        }
+        released.push(*shortcut);
+    }
+
+    (shortcuts, released, errors)
+}
+
+fn clear_session(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let state = tauri::Manager::state::<AppSession>(app);
+    *state.session.blocking_lock() = Session::default();
+    Ok(())
+}
+
+fn setup_cache(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let config = state.config.blocking_lock();
+
+    let cache_size = std::num::NonZeroUsize::try_from(config.cache_size)?;
+    let cache = lru::LruCache::new(cache_size);
+
+    let cache = tauri::async_runtime::Mutex::new(cache);
+    let state = AppCache { cache };
+    tauri::Manager::manage(app, state);
+
+    Ok(())
+}
+
+fn setup_history(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let filepath =
+        tauri::Manager::path(app).resolve("history.json", tauri::path::BaseDirectory::AppData)?;
+
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let config = state.config.blocking_lock();
+
+    let mut history = history::History::load(filepath);
+    history.retain(config.history_size, config.history_days);
+
+    let history = tauri::async_runtime::Mutex::new(history);
+    let state = AppHistory { history };
+    tauri::Manager::manage(app, state);
+
+    Ok(())
+}
+
+fn setup_channel(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let config = state.config.blocking_lock();
+
+    let min_duration = std::time::Duration::from_secs_f64(config.rate);
+
+    let (tx_input, rx_input) = crossbeam_channel::unbounded::<Query>();
+
+    let app_clone = app.clone();
+    let _th_input = tauri::async_runtime::spawn(async move {
+        loop {
+            let Ok(mut query) = rx_input.recv() else {
+                continue;
+            };
+
+            if !rx_input.is_empty() {
+                continue;
+            }
+
+            log::info!("input: {}", query.input);
+            let instant = std::time::Instant::now();
+
+            let cache = tauri::Manager::state::<AppCache>(&app_clone);
+
+            // clone not to hold the lock over requests, commands lock it on main thread
+            let state = tauri::Manager::state::<AppConfig>(&app_clone);
+            let config = state.config.lock().await.clone();
+
+            query.input = query.input.trim().into();
+
+            // bare command such as "/ja" or cleared input, clear the output without request
+            if query.input.is_empty() {
+                let output = Output {
+                    outputs: vec![],
+                    diffs: vec![],
+                    language: query.language.clone(),
+                    detected: None,
+                    suspect: false,
+                };
+                match tauri::Emitter::emit(&app_clone, "update_output", output) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::error!("error occured {}", e);
+                    }
+                }
+                continue;
+            }
+
+            let outputs = match request_outputs(&cache.cache, &query, &config).await {
+                Ok(outputs) => outputs,
+                Err(e) => {
+                    log::error!("error occured {}", e);
+                    continue;
+                }
+            };
+            let suspect = outputs.suspect;
+            let (outputs, feedbacks) = match split_explanation(&query, outputs.outputs) {
+                Ok(outputs) => outputs,
                 Err(e) => {
                     log::error!("error occured {}", e);
                     continue;
                 }
             };
+
+            // show what polishing changed, hunks can be rejected in window main
+            let diffs = if query.mode == MODE_POLISHING || query.mode == MODE_EXPLANATION {
+                outputs
+                    .iter()
+                    .map(|output| diff::diff(&query.input, output))
+                    .collect()
+            } else {
+                vec![]
+            };
+
+            let output = Output {
+                outputs,
+                diffs,
+                language: query.language.clone(),
+                detected: language::detect(&query.input),
+                suspect,
+            };
             match tauri::Emitter::emit(&app_clone, "update_output", output) {
                 Ok(_) => {}
                 Err(e) => {
@@ -247,6 +1119,13 @@ fn setup_channel(app: &tauri::AppHandle) -> anyhow::Result<()> {
                     continue;
                 }
             }
+            match tauri::Emitter::emit(&app_clone, "update_feedback", feedbacks) {
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!("error occured {}", e);
+                    continue;
+                }
+            }
 
             let duration = min_duration.saturating_sub(instant.elapsed());
             tokio::time::sleep(duration).await;
@@ -262,6 +1141,48 @@ fn setup_channel(app: &tauri::AppHandle) -> anyhow::Result<()> {
     Ok(())
 }
 
+// popup sends every keystroke, so reuse sentences which are not edited
+async fn request_outputs(
+    cache: &tauri::async_runtime::Mutex<lru::LruCache<Query, llm::Outputs>>,
+    query: &Query,
+    config: &Config,
+) -> anyhow::Result<llm::Outputs> {
+    if config.incremental {
+        llm::request_llm_incremental(cache, query, config).await
+    } else {
+        llm::request_llm_chunked(cache, query, config).await
+    }
+}
+
+// explanation mode replies json, split it into sentences and issues
+fn split_explanation(
+    query: &Query,
+    outputs: Vec<String>,
+) -> anyhow::Result<(Vec<String>, Vec<Vec<llm::Issue>>)> {
+    if query.mode != MODE_EXPLANATION {
+        return Ok((outputs, vec![]));
+    }
+
+    let mut sentences = vec![];
+    let mut feedbacks = vec![];
+    for output in outputs {
+        match llm::parse_explanation(&query.input, &output) {
+            Ok(explanation) => {
+                sentences.push(explanation.sentence);
+                feedbacks.push(explanation.issues);
+            }
+            Err(e) => {
+                log::error!("error occured {}", e);
+            }
+        }
+    }
+
+    if sentences.is_empty() {
+        return Err(anyhow::anyhow!("reply of explanation mode is invalid"));
+    }
+    Ok((sentences, feedbacks))
+}
+
 fn setup_tray(app: &tauri::AppHandle) -> anyhow::Result<()> {
     let icon = app
         .default_window_icon()
@@ -269,9 +1190,12 @@ fn setup_tray(app: &tauri::AppHandle) -> anyhow::Result<()> {
         .clone();
 
     let menu_show = tauri::menu::MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
+    let menu_history =
+        tauri::menu::MenuItem::with_id(app, "history", "History", true, None::<&str>)?;
     let menu_config = tauri::menu::MenuItem::with_id(app, "config", "Config", true, None::<&str>)?;
     let menu_quit = tauri::menu::MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-    let menu = tauri::menu::Menu::with_items(app, &[&menu_show, &menu_config, &menu_quit])?;
+    let menu =
+        tauri::menu::Menu::with_items(app, &[&menu_show, &menu_history, &menu_config, &menu_quit])?;
 
     tauri::tray::TrayIconBuilder::new()
         .icon(icon)
@@ -288,7 +1212,16 @@ fn tray_handle(app: &tauri::AppHandle, event: tauri::menu::MenuEvent) {
     match event.id().as_ref() {
         "show" => {
             log::info!("send event: show window main");
-            match show_window_main(app) {
+            match clear_session(app).and_then(|_| show_window_main(app)) {
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!("error occured {}", e);
+                }
+            };
+        }
+        "history" => {
+            log::info!("send event: setup window history");
+            match create_window_history(app) {
                 Ok(_) => {}
                 Err(e) => {
                     log::error!("error occured {}", e);
@@ -324,6 +1257,16 @@ fn create_window_config(app: &tauri::AppHandle) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn create_window_history(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let url = tauri::WebviewUrl::App("history".into());
+    tauri::WebviewWindowBuilder::new(app, "history", url)
+        .title("Quicklime")
+        .min_inner_size(480.0, 270.0)
+        .build()?;
+
+    Ok(())
+}
+
 pub fn setup_window_event_handle() -> anyhow::Result<impl Fn(&tauri::Window, &tauri::WindowEvent)> {
     // no occur panic in handle fn
     let handle = |window: &tauri::Window, event: &tauri::WindowEvent| {
@@ -360,60 +1303,281 @@ fn hide_window_main(app: &tauri::AppHandle) -> anyhow::Result<()> {
 pub fn on_confirm_input(app: tauri::AppHandle, input: String) {
     log::info!("confirm input {}", input);
 
-    match confirm_input(&app, input) {
+    // typing blocks until the last character, so run it off the main thread
+    tauri::async_runtime::spawn_blocking(move || match confirm_input(&app, input) {
         Ok(_) => {}
         Err(e) => {
             log::error!("error occured {}", e);
-            return;
         }
-    }
+    });
 }
 
 fn confirm_input(app: &tauri::AppHandle, input: String) -> anyhow::Result<()> {
     hide_window_main(app)?;
-    paste_clipboard(app, &input)?;
+
+    let state = tauri::Manager::state::<AppSession>(app);
+    let session = state.session.blocking_lock().clone();
+
+    let source = session.query.as_ref().map(|query| query.input.clone());
+    insert_output(app, &input, source)?;
+
+    if let Some(query) = session.query {
+        let info = session.app.unwrap_or_default();
+        record_history(app, &query, input, info.process)?;
+    }
+
+    clear_turns(app)?;
+
     Ok(())
 }
 
+fn record_history(
+    app: &tauri::AppHandle,
+    query: &Query,
+    output: String,
+    process: String,
+) -> anyhow::Result<()> {
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let config = state.config.blocking_lock().clone();
+    if !config.history_enabled {
+        return Ok(());
+    }
+
+    log::info!("record history");
+    let state = tauri::Manager::state::<AppHistory>(app);
+    let mut history = state.history.blocking_lock();
+    history.push(query, output, process);
+    history.retain(config.history_size, config.history_days);
+    history.save()?;
+
+    Ok(())
+}
+
+// insert into the window which opened window main, source is restored by undo
+fn insert_output(
+    app: &tauri::AppHandle,
+    input: &str,
+    source: Option<String>,
+) -> anyhow::Result<()> {
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let config = state.config.blocking_lock().clone();
+
+    let state = tauri::Manager::state::<AppSession>(app);
+    let session = state.session.blocking_lock().clone();
+
+    let platform = get_platform(app);
+    let insertion = match insert_session_output(&platform, &config, &session, input, source) {
+        Ok(insertion) => insertion,
+        Err(e) => {
+            notify(app, &format!("Failed to paste output: {}", e));
+            return Err(e);
+        }
+    };
+    notify_clipboard_only(app, &config, &session.profile.unwrap_or_default());
+
+    let state = tauri::Manager::state::<AppInsertion>(app);
+    *state.insertion.blocking_lock() = insertion;
+
+    Ok(())
+}
+
+// give focus back to the window which opened window main, then insert
+fn insert_session_output(
+    platform: &std::sync::Arc<dyn platform::Platform>,
+    config: &Config,
+    session: &Session,
+    output: &str,
+    source: Option<String>,
+) -> anyhow::Result<Option<Insertion>> {
+    let profile = session.profile.clone().unwrap_or_default();
+
+    if let Some(target) = session.target {
+        restore_focus(platform.as_ref(), target)?;
+    }
+
+    insert_text(platform, output, config, &profile)?;
+
+    Ok(create_insertion(
+        config,
+        session.target,
+        profile,
+        source,
+        output,
+    ))
+}
+
+// focus may land on another window after hiding window main
+fn restore_focus(
+    platform: &dyn platform::Platform,
+    target: platform::WindowId,
+) -> anyhow::Result<()> {
+    log::info!("restore focus {:?}", target);
+
+    platform.focus_window(target)?;
+
+    // wait for the window manager to activate the window
+    const FOCUS_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+    const FOCUS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(20);
+    let instant = std::time::Instant::now();
+    while platform.get_foreground_window().ok() != Some(target) {
+        if instant.elapsed() >= FOCUS_TIMEOUT {
+            return Err(anyhow::anyhow!(
+                "focus is not restored to the original window"
+            ));
+        }
+        std::thread::sleep(FOCUS_INTERVAL);
+    }
+
+    Ok(())
+}
+
+fn insert_method(config: &Config, profile: &Profile) -> usize {
+    profile.insert_method.unwrap_or(config.insert_method)
+}
+
+fn notify_clipboard_only(app: &tauri::AppHandle, config: &Config, profile: &Profile) {
+    if insert_method(config, profile) == INSERT_CLIPBOARD {
+        notify(app, "Output is copied to clipboard, paste it manually.");
+    }
+}
+
+// terminals expect ctrl shift v, and wrap the paste in bracketed paste markers by themselves
+fn insert_text(
+    platform: &std::sync::Arc<dyn platform::Platform>,
+    text: &str,
+    config: &Config,
+    profile: &Profile,
+) -> anyhow::Result<()> {
+    match insert_method(config, profile) {
+        INSERT_PASTE => paste_clipboard(platform, text, platform::PasteKey::CtrlV, config),
+        INSERT_TYPE => type_text(platform, text, config),
+        INSERT_PASTE_CTRL_SHIFT_V => {
+            paste_clipboard(platform, text, platform::PasteKey::CtrlShiftV, config)
+        }
+        INSERT_PASTE_SHIFT_INSERT => {
+            paste_clipboard(platform, text, platform::PasteKey::ShiftInsert, config)
+        }
+        INSERT_CLIPBOARD => {
+            log::info!("copy output to clipboard");
+            platform.write_clipboard(&platform::ClipboardContent::Text(text.into()))?;
+            Ok(())
+        }
+        method => Err(anyhow::anyhow!("insert method {} is invalid", method)),
+    }
+}
+
 // PowerToys at https://github.com/microsoft/PowerToys/blob/9f008a65d604313159e1e83607e8240b0d49098d/src/modules/AdvancedPaste/AdvancedPaste/Helpers/ClipboardHelper.cs#L89
-fn paste_clipboard(app: &tauri::AppHandle, text: &str) -> anyhow::Result<()> {
+fn paste_clipboard(
+    platform: &std::sync::Arc<dyn platform::Platform>,
+    text: &str,
+    key: platform::PasteKey,
+    config: &Config,
+) -> anyhow::Result<()> {
     log::info!("paste clipboard {}", text);
 
-    tauri_plugin_clipboard_manager::ClipboardExt::clipboard(app).write_text(text)?;
+    // config is not validated, negative or nan delay is rejected before touching clipboard
+    let delay = std::time::Duration::try_from_secs_f64(config.restore_clipboard_delay)
+        .context("restore clipboard delay is invalid")?;
+
+    let content = platform.read_clipboard()?;
+    platform.write_clipboard(&platform::ClipboardContent::Text(text.into()))?;
 
-    // key up modifier keys
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
-    send_input(VK_LCONTROL, KEYEVENTF_KEYUP);
-    send_input(VK_RCONTROL, KEYEVENTF_KEYUP);
-    send_input(VK_LWIN, KEYEVENTF_KEYUP);
-    send_input(VK_RWIN, KEYEVENTF_KEYUP);
-    send_input(VK_LSHIFT, KEYEVENTF_KEYUP);
-    send_input(VK_RSHIFT, KEYEVENTF_KEYUP);
-    send_input(VK_LMENU, KEYEVENTF_KEYUP);
-    send_input(VK_RMENU, KEYEVENTF_KEYUP);
-    // send ctrl v
-    send_input(VK_CONTROL, Default::default());
-    send_input(VK_V, Default::default());
-    send_input(VK_V, KEYEVENTF_KEYUP);
-    send_input(VK_CONTROL, KEYEVENTF_KEYUP);
+    platform.send_paste(key)?;
+
+    if config.restore_clipboard {
+        restore_clipboard_later(platform.clone(), content, text.into(), delay);
+    }
 
     Ok(())
 }
 
-// SendInput at https://learn.microsoft.com/ja-jp/windows/win32/api/winuser/nf-winuser-sendinput
-fn send_input(
-    key_code: windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY,
-    flags: windows::Win32::UI::Input::KeyboardAndMouse::KEYBD_EVENT_FLAGS,
+// some targets block paste, e.g. remote desktop or password field
+// typing waits the interval per character, callers run it off the main thread
+fn type_text(
+    platform: &std::sync::Arc<dyn platform::Platform>,
+    text: &str,
+    config: &Config,
+) -> anyhow::Result<()> {
+    log::info!("type text {}", text);
+
+    let interval = std::time::Duration::try_from_secs_f64(config.type_interval)
+        .context("type interval is invalid")?;
+
+    platform.send_text(text, interval)
+}
+
+fn copy_clipboard(platform: &dyn platform::Platform) -> anyhow::Result<String> {
+    log::info!("copy clipboard");
+
+    // clear clipboard to detect no selection
+    let content = platform.read_clipboard()?;
+    platform.write_clipboard(&platform::ClipboardContent::Empty)?;
+
+    platform.send_copy()?;
+
+    // wait for the focus window to update clipboard
+    const COPY_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+    const COPY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(20);
+    let instant = std::time::Instant::now();
+    let mut text = None;
+    while instant.elapsed() < COPY_TIMEOUT {
+        std::thread::sleep(COPY_INTERVAL);
+        if let Ok(platform::ClipboardContent::Text(copied)) = platform.read_clipboard() {
+            text = Some(copied);
+            break;
+        }
+    }
+
+    platform.write_clipboard(&content)?;
+
+    text.context("no text is selected")
+}
+
+// wait for the focus window to read clipboard before restore
+fn restore_clipboard_later(
+    platform: std::sync::Arc<dyn platform::Platform>,
+    content: platform::ClipboardContent,
+    text: String,
+    delay: std::time::Duration,
 ) {
-    const EXTRA_INFO: usize = 0x5555;
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(delay).await;
+
+        // skip when the user copies something else in the meantime
+        match platform.read_clipboard() {
+            Ok(platform::ClipboardContent::Text(current)) if current == text => {}
+            _ => {
+                log::info!("skip restore clipboard");
+                return;
+            }
+        }
 
-    let mut pinput = windows::Win32::UI::Input::KeyboardAndMouse::INPUT::default();
-    pinput.r#type = windows::Win32::UI::Input::KeyboardAndMouse::INPUT_KEYBOARD;
-    pinput.Anonymous.ki.wVk = key_code;
-    pinput.Anonymous.ki.dwFlags = flags;
-    pinput.Anonymous.ki.dwExtraInfo = EXTRA_INFO;
-    let cbsize = std::mem::size_of_val(&pinput) as i32;
-    unsafe { windows::Win32::UI::Input::KeyboardAndMouse::SendInput(&[pinput], cbsize) };
+        log::info!("restore clipboard");
+        match platform.write_clipboard(&content) {
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("error occured {}", e);
+            }
+        }
+    });
+}
+
+// selected text is held in primary selection without explicit copy on X11
+fn capture_selection(platform: &dyn platform::Platform) -> anyhow::Result<String> {
+    log::info!("capture selection");
+
+    // no owner or empty selection falls back to synthetic copy
+    let text = match platform.read_primary_selection() {
+        Ok(text) => text,
+        Err(e) => {
+            log::error!("error occured {}", e);
+            None
+        }
+    };
+    match text.filter(|text| !text.trim().is_empty()) {
+        Some(text) => Ok(text),
+        None => copy_clipboard(platform),
+    }
 }
 
 // no occur panic in handle fn
@@ -432,6 +1596,46 @@ pub fn on_exit_input(app: tauri::AppHandle) {
 
 fn exit_input(app: &tauri::AppHandle) -> anyhow::Result<()> {
     hide_window_main(app)?;
+    clear_turns(app)?;
+    Ok(())
+}
+
+// no occur panic in handle fn
+#[tauri::command]
+pub fn on_refine_input(app: tauri::AppHandle, output: String, instruction: String) {
+    log::info!("refine input {}", instruction);
+
+    match refine_input(&app, output, instruction) {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("error occured {}", e);
+            return;
+        }
+    }
+}
+
+// continue conversation with the output and instruction until confirm or exit
+fn refine_input(app: &tauri::AppHandle, output: String, instruction: String) -> anyhow::Result<()> {
+    let state = tauri::Manager::state::<AppSession>(app);
+    let mut session = state.session.blocking_lock();
+    let query = session.query.as_mut().context("no output to refine")?;
+    query.turns.push(Turn {
+        output,
+        instruction,
+    });
+    let query = query.clone();
+    drop(session);
+
+    let state = tauri::Manager::state::<AppChannel>(app);
+    state.tx_input.send(query)?;
+    Ok(())
+}
+
+fn clear_turns(app: &tauri::AppHandle) -> anyhow::Result<()> {
+    let state = tauri::Manager::state::<AppSession>(app);
+    if let Some(query) = state.session.blocking_lock().query.as_mut() {
+        query.turns.clear();
+    }
     Ok(())
 }
 
@@ -450,26 +1654,110 @@ pub fn on_change_input(app: tauri::AppHandle, input: String, mode: usize) {
 }
 
 fn change_input(app: &tauri::AppHandle, input: String, mode: usize) -> anyhow::Result<()> {
+    let state = tauri::Manager::state::<AppSession>(app);
+    let profile = state.session.blocking_lock().profile.clone();
+    let profile = profile.unwrap_or_default();
+
+    let suggestions = command::suggest(&input);
+    tauri::Emitter::emit(app, "update_suggestions", suggestions)?;
+
+    // slash commands override mode, language and style per request
+    let (command, input) = command::parse(&input);
+    let mode = command.mode.unwrap_or(mode);
+
+    let state = tauri::Manager::state::<AppConfig>(app);
+    let config = state.config.blocking_lock().clone();
+    let mut query = build_query(&config, &profile, input.into(), mode)?;
+    match command.language {
+        Some(language) => query.language = language,
+        None => apply_language_pair(&config, &mut query),
+    }
+    if let Some(style) = command.style {
+        query.style = Some(style);
+    }
+
+    let state = tauri::Manager::state::<AppSession>(app);
+    state.session.blocking_lock().query = Some(query.clone());
+
     let state = tauri::Manager::state::<AppChannel>(app);
-    state.tx_input.send((input, mode))?;
+    state.tx_input.send(query)?;
     Ok(())
 }
 
+fn build_query(
+    config: &Config,
+    profile: &Profile,
+    input: String,
+    mode: usize,
+) -> anyhow::Result<Query> {
+    if !MODES.contains(&mode) {
+        return Err(anyhow::anyhow!("mode {} is invalid", mode));
+    }
+    if profile.disabled_modes.contains(&mode) {
+        return Err(anyhow::anyhow!("mode {} is disabled by profile", mode));
+    }
+
+    // profiles of old configs are not validated, the worker must not panic on them
+    let llm = profile.llm.unwrap_or(config.llm);
+    if !LLMS.contains(&llm) {
+        return Err(anyhow::anyhow!("llm {} is invalid", llm));
+    }
+
+    let query = Query {
+        input,
+        mode,
+        llm,
+        language: profile
+            .language
+            .clone()
+            .unwrap_or_else(|| config.language.clone()),
+        style: profile.style.clone(),
+        turns: vec![],
+        context: Default::default(),
+        strict: false,
+    };
+    Ok(query)
+}
+
+// text already in the target language is translated into the secondary language
+fn apply_language_pair(config: &Config, query: &mut Query) {
+    let secondary_language = config.secondary_language.trim();
+    if query.mode != MODE_TRANSLATION || secondary_language.is_empty() {
+        return;
+    }
+
+    // only the pair is compared, so short input is also detected
+    let languages = [query.language.as_str(), secondary_language];
+    if language::closest(&query.input, &languages) == Some(0) {
+        log::info!(
+            "input is in {}, translate into {}",
+            query.language,
+            secondary_language
+        );
+        query.language = secondary_language.into();
+    }
+}
+
 // no occur panic in handle fn
+// rejected config is reported to window config
 #[tauri::command]
-pub fn on_change_config(app: tauri::AppHandle, config: Config) {
+pub fn on_change_config(app: tauri::AppHandle, config: Config) -> Result<(), String> {
     log::info!("change config {:?}", config);
 
     match change_config(&app, config) {
         Ok(_) => {}
         Err(e) => {
             log::error!("error occured {}", e);
-            return;
+            return Err(format!("{:#}", e));
         }
     }
+
+    Ok(())
 }
 
 fn change_config(app: &tauri::AppHandle, config: Config) -> anyhow::Result<()> {
+    config.validate()?;
+
     let state = tauri::Manager::state::<AppConfig>(app);
     *state.config.blocking_lock() = config.clone();
 
@@ -484,6 +1772,15 @@ fn change_config(app: &tauri::AppHandle, config: Config) -> anyhow::Result<()> {
     // reset cache
     setup_cache(app)?;
 
+    // apply retention of new config
+    let state = tauri::Manager::state::<AppHistory>(app);
+    let mut history = state.history.blocking_lock();
+    history.retain(config.history_size, config.history_days);
+    history.save()?;
+    drop(history);
+
+    register_shortcuts(app)?;
+
     Ok(())
 }
 
@@ -508,3 +1805,701 @@ fn get_config(app: &tauri::AppHandle) -> anyhow::Result<Config> {
     let config = state.config.blocking_lock().clone();
     Ok(config)
 }
+
+// no occur panic in handle fn
+#[tauri::command]
+pub fn on_get_shortcut_errors(app: tauri::AppHandle) -> Option<Vec<String>> {
+    log::info!("get shortcut errors");
+
+    let errors = match get_shortcut_errors(&app) {
+        Ok(errors) => errors,
+        Err(e) => {
+            log::error!("error occured {}", e);
+            return None;
+        }
+    };
+
+    Some(errors)
+}
+
+fn get_shortcut_errors(app: &tauri::AppHandle) -> anyhow::Result<Vec<String>> {
+    let state = tauri::Manager::state::<AppShortcut>(app);
+    let errors = state.errors.blocking_lock().clone();
+    Ok(errors)
+}
+
+// no occur panic in handle fn
+#[tauri::command]
+pub fn on_get_history(app: tauri::AppHandle, text: String) -> Option<Vec<history::Entry>> {
+    log::info!("get history {}", text);
+
+    let entries = match get_history(&app, text) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("error occured {}", e);
+            return None;
+        }
+    };
+
+    Some(entries)
+}
+
+fn get_history(app: &tauri::AppHandle, text: String) -> anyhow::Result<Vec<history::Entry>> {
+    let state = tauri::Manager::state::<AppHistory>(app);
+    let entries = state.history.blocking_lock().search(&text);
+    Ok(entries)
+}
+
+// no occur panic in handle fn
+#[tauri::command]
+pub fn on_paste_history(app: tauri::AppHandle, id: u64) {
+    log::info!("paste history {}", id);
+
+    // typing blocks until the last character, so run it off the main thread
+    tauri::async_runtime::spawn_blocking(move || match paste_history(&app, id) {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("error occured {}", e);
+        }
+    });
+}
+
+// paste into the window which opened window main last time
+// copy instead when the session has no such window, e.g. after show from tray
+fn paste_history(app: &tauri::AppHandle, id: u64) -> anyhow::Result<()> {
+    let entry = get_history_entry(app, id)?;
+
+    if let Some(window) = tauri::Manager::get_webview_window(app, "history") {
+        window.close()?;
+    }
+
+    let state = tauri::Manager::state::<AppSession>(app);
+    let session = state.session.blocking_lock().clone();
+    let platform = get_platform(app);
+    if history_target(platform.as_ref(), &session).is_none() {
+        platform.write_clipboard(&platform::ClipboardContent::Text(entry.output))?;
+        notify(
+            app,
+            "No window to paste into, output is copied to clipboard.",
+        );
+        return Ok(());
+    }
+
+    insert_output(app, &entry.output, Some(entry.input))?;
+    Ok(())
+}
+
+// the window may be closed after window main was hidden
+fn history_target(
+    platform: &dyn platform::Platform,
+    session: &Session,
+) -> Option<platform::WindowId> {
+    let target = session.target?;
+    platform.get_window_rect(target).ok()?;
+    Some(target)
+}
+
+// no occur panic in handle fn
+#[tauri::command]
+pub fn on_copy_history(app: tauri::AppHandle, id: u64) {
+    log::info!("copy history {}", id);
+
+    match copy_history(&app, id) {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("error occured {}", e);
+            return;
+        }
+    }
+}
+
+fn copy_history(app: &tauri::AppHandle, id: u64) -> anyhow::Result<()> {
+    let entry = get_history_entry(app, id)?;
+    get_platform(app).write_clipboard(&platform::ClipboardContent::Text(entry.output))?;
+    Ok(())
+}
+
+fn get_history_entry(app: &tauri::AppHandle, id: u64) -> anyhow::Result<history::Entry> {
+    let state = tauri::Manager::state::<AppHistory>(app);
+    let history = state.history.blocking_lock();
+    let entry = history.get(id).context("history entry is not found")?;
+    Ok(entry.clone())
+}
+
+// no occur panic in handle fn
+#[tauri::command]
+pub fn on_delete_history(app: tauri::AppHandle, id: u64) {
+    log::info!("delete history {}", id);
+
+    match delete_history(&app, id) {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("error occured {}", e);
+            return;
+        }
+    }
+}
+
+fn delete_history(app: &tauri::AppHandle, id: u64) -> anyhow::Result<()> {
+    let state = tauri::Manager::state::<AppHistory>(app);
+    let mut history = state.history.blocking_lock();
+    history.remove(id);
+    history.save()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_info(process: &str, class: &str) -> platform::AppInfo {
+        platform::AppInfo {
+            process: process.into(),
+            class: class.into(),
+        }
+    }
+
+    fn profile(app: &str) -> Profile {
+        Profile {
+            app: app.into(),
+            ..Default::default()
+        }
+    }
+
+    const TARGET: platform::WindowId = platform::WindowId(1);
+    const MAIN: platform::WindowId = platform::WindowId(2);
+
+    // editor in the foreground and window main of this app behind it
+    fn mock() -> std::sync::Arc<platform::Mock> {
+        let target = platform::MockWindow {
+            id: TARGET,
+            info: app_info("editor", "Editor"),
+            selection: "Hello".into(),
+            ..Default::default()
+        };
+        let main = platform::MockWindow {
+            id: MAIN,
+            own: true,
+            ..Default::default()
+        };
+        let state = platform::MockState {
+            windows: vec![target, main],
+            foreground: Some(TARGET),
+            clipboard: platform::ClipboardContent::Text("previous".into()),
+            primary_selection: None,
+            block_input: false,
+        };
+        std::sync::Arc::new(platform::Mock::new(state))
+    }
+
+    fn pasted(mock: &platform::Mock, window: platform::WindowId) -> Vec<String> {
+        let state = mock.state.lock().unwrap();
+        let window = state.windows.iter().find(|other| other.id == window);
+        window
+            .map(|window| window.pasted.clone())
+            .unwrap_or_default()
+    }
+
+    fn clipboard_text(mock: &platform::Mock) -> Option<String> {
+        match &mock.state.lock().unwrap().clipboard {
+            platform::ClipboardContent::Text(text) => Some(text.clone()),
+            _ => None,
+        }
+    }
+
+    // window main takes focus while the user types
+    fn focus_main(mock: &platform::Mock) {
+        mock.state.lock().unwrap().foreground = Some(MAIN);
+    }
+
+    fn confirm(
+        mock: &std::sync::Arc<platform::Mock>,
+        config: &Config,
+        session: &Session,
+        output: &str,
+    ) -> anyhow::Result<Option<Insertion>> {
+        let platform: std::sync::Arc<dyn platform::Platform> = mock.clone();
+        insert_session_output(&platform, config, session, output, Some("Hello".into()))
+    }
+
+    // pop over the editor, then confirm the output from window main
+    fn pop_and_confirm(
+        mock: &std::sync::Arc<platform::Mock>,
+        config: &Config,
+        output: &str,
+    ) -> anyhow::Result<Option<Insertion>> {
+        let session = open_session(mock.as_ref(), config, None)?;
+        focus_main(mock);
+        confirm(mock, config, &session, output)
+    }
+
+    // background tasks like the clipboard restore finish within the timeout
+    fn wait_until(condition: impl Fn() -> bool) -> bool {
+        let start = std::time::Instant::now();
+        while start.elapsed() < std::time::Duration::from_secs(5) {
+            if condition() {
+                return true;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        condition()
+    }
+
+    #[test]
+    fn flow_pop_confirm_paste() {
+        let mock = mock();
+        let config = Config {
+            restore_clipboard_delay: 0.0,
+            ..Default::default()
+        };
+
+        let session = open_session(mock.as_ref(), &config, Some(MODE_TRANSLATION)).unwrap();
+        assert_eq!(session.target, Some(TARGET));
+        assert_eq!(session.mode, Some(MODE_TRANSLATION));
+        assert!(session.input.is_none());
+
+        focus_main(&mock);
+        let insertion = confirm(&mock, &config, &session, "Bonjour").unwrap();
+
+        assert_eq!(pasted(&mock, TARGET), vec!["Bonjour".to_string()]);
+        assert!(pasted(&mock, MAIN).is_empty());
+        assert_eq!(mock.state.lock().unwrap().foreground, Some(TARGET));
+        let insertion = insertion.unwrap();
+        assert_eq!(insertion.target, Some(TARGET));
+        assert_eq!(insertion.input.as_deref(), Some("Hello"));
+
+        // clipboard is restored in the background after the delay
+        assert!(wait_until(
+            || clipboard_text(&mock).as_deref() == Some("previous")
+        ));
+    }
+
+    #[test]
+    fn flow_keep_clipboard() {
+        let mock = mock();
+        let config = Config {
+            restore_clipboard: false,
+            ..Default::default()
+        };
+
+        pop_and_confirm(&mock, &config, "Bonjour").unwrap();
+        assert_eq!(clipboard_text(&mock).as_deref(), Some("Bonjour"));
+    }
+
+    #[test]
+    fn flow_prefill_selection() {
+        let mock = mock();
+        let config = Config {
+            prefill_selection: true,
+            restore_clipboard: false,
+            ..Default::default()
+        };
+
+        let session = open_session(mock.as_ref(), &config, None).unwrap();
+        assert_eq!(session.input.as_deref(), Some("Hello"));
+        assert_eq!(clipboard_text(&mock).as_deref(), Some("previous"));
+    }
+
+    #[test]
+    fn flow_profile_insert_method() {
+        let mock = mock();
+        let mut profile = profile("editor");
+        profile.insert_method = Some(INSERT_TYPE);
+        let config = Config {
+            profiles: vec![profile],
+            ..Default::default()
+        };
+
+        let session = open_session(mock.as_ref(), &config, None).unwrap();
+        assert_eq!(
+            session.profile.as_ref().map(|profile| profile.app.as_str()),
+            Some("editor")
+        );
+
+        pop_and_confirm(&mock, &config, "Bonjour").unwrap();
+        assert_eq!(pasted(&mock, TARGET), vec!["Bonjour".to_string()]);
+        assert_eq!(clipboard_text(&mock).as_deref(), Some("previous"));
+    }
+
+    #[test]
+    fn flow_invalid_type_interval() {
+        let mock = mock();
+        let config = Config {
+            insert_method: INSERT_TYPE,
+            type_interval: -0.01,
+            ..Default::default()
+        };
+
+        assert!(pop_and_confirm(&mock, &config, "Bonjour").is_err());
+        assert!(pasted(&mock, TARGET).is_empty());
+    }
+
+    #[test]
+    fn flow_type_failure() {
+        let mock = mock();
+        let config = Config {
+            insert_method: INSERT_TYPE,
+            ..Default::default()
+        };
+
+        mock.state.lock().unwrap().block_input = true;
+        let result = pop_and_confirm(&mock, &config, "Bonjour");
+        assert!(result.unwrap_err().to_string().contains("blocked"));
+        assert!(pasted(&mock, TARGET).is_empty());
+    }
+
+    #[test]
+    fn flow_clipboard_only() {
+        let mock = mock();
+        let config = Config {
+            insert_method: INSERT_CLIPBOARD,
+            ..Default::default()
+        };
+
+        let insertion = pop_and_confirm(&mock, &config, "Bonjour").unwrap();
+        assert!(insertion.is_none());
+        assert!(pasted(&mock, TARGET).is_empty());
+        assert_eq!(clipboard_text(&mock).as_deref(), Some("Bonjour"));
+    }
+
+    #[test]
+    fn flow_invalid_restore_delay() {
+        for delay in [-1.0, f64::NAN, f64::INFINITY] {
+            let mock = mock();
+            let config = Config {
+                restore_clipboard_delay: delay,
+                ..Default::default()
+            };
+
+            assert!(pop_and_confirm(&mock, &config, "Bonjour").is_err());
+            assert!(pasted(&mock, TARGET).is_empty());
+            assert_eq!(clipboard_text(&mock).as_deref(), Some("previous"));
+        }
+    }
+
+    #[test]
+    fn flow_undo_backspace() {
+        let mock = mock();
+        let config = Config {
+            undo_with_backspace: true,
+            restore_clipboard: false,
+            ..Default::default()
+        };
+
+        let output = "caf\u{65}\u{301} \u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\r\n";
+        let insertion = pop_and_confirm(&mock, &config, output).unwrap().unwrap();
+        assert_eq!(insertion.length, 7);
+
+        let platform: std::sync::Arc<dyn platform::Platform> = mock.clone();
+        revert_insertion(&platform, &config, insertion).unwrap();
+        assert_eq!(pasted(&mock, TARGET), vec!["".to_string()]);
+    }
+
+    #[test]
+    fn flow_undo_typed_controls() {
+        let mock = mock();
+        let config = Config {
+            insert_method: INSERT_TYPE,
+            undo_with_backspace: true,
+            ..Default::default()
+        };
+        mock.state.lock().unwrap().windows[0].pasted = vec!["user text ".into()];
+
+        let output = "Bon\u{1b}jour\r\n\u{7}!";
+        let insertion = pop_and_confirm(&mock, &config, output).unwrap().unwrap();
+        assert_eq!(
+            pasted(&mock, TARGET),
+            vec!["user text ".to_string(), "Bonjour\n!".to_string()]
+        );
+        assert_eq!(insertion.length, 9);
+
+        let platform: std::sync::Arc<dyn platform::Platform> = mock.clone();
+        revert_insertion(&platform, &config, insertion).unwrap();
+        assert_eq!(
+            pasted(&mock, TARGET),
+            vec!["user text ".to_string(), "".to_string()]
+        );
+    }
+
+    #[test]
+    fn flow_undo_refused() {
+        let mock = mock();
+        let config = Config::default();
+        let platform: std::sync::Arc<dyn platform::Platform> = mock.clone();
+
+        let insertion = pop_and_confirm(&mock, &config, "Bonjour").unwrap().unwrap();
+
+        // focus moved to another window
+        focus_main(&mock);
+        assert!(revert_insertion(&platform, &config, insertion.clone()).is_err());
+
+        // target is unknown, e.g. on wayland
+        mock.state.lock().unwrap().foreground = Some(TARGET);
+        let unknown = Insertion {
+            target: None,
+            ..insertion
+        };
+        assert!(revert_insertion(&platform, &config, unknown).is_err());
+        assert_eq!(pasted(&mock, TARGET), vec!["Bonjour".to_string()]);
+    }
+
+    #[test]
+    fn flow_prefill_primary_selection() {
+        let mock = mock();
+        let config = Config {
+            prefill_selection: true,
+            ..Default::default()
+        };
+
+        mock.state.lock().unwrap().primary_selection = Some("Selected".into());
+        let session = open_session(mock.as_ref(), &config, None).unwrap();
+        assert_eq!(session.input.as_deref(), Some("Selected"));
+
+        // empty primary selection falls back to copy
+        mock.state.lock().unwrap().primary_selection = Some(" \n".into());
+        let session = open_session(mock.as_ref(), &config, None).unwrap();
+        assert_eq!(session.input.as_deref(), Some("Hello"));
+    }
+
+    #[test]
+    fn flow_pop_over_own_window() {
+        let mock = mock();
+        focus_main(&mock);
+        assert!(open_session(mock.as_ref(), &Config::default(), None).is_err());
+    }
+
+    #[test]
+    fn flow_target_closed() {
+        let mock = mock();
+        let config = Config::default();
+
+        let session = open_session(mock.as_ref(), &config, None).unwrap();
+        mock.state
+            .lock()
+            .unwrap()
+            .windows
+            .retain(|window| window.id != TARGET);
+        focus_main(&mock);
+        assert!(confirm(&mock, &config, &session, "Bonjour").is_err());
+        assert!(pasted(&mock, MAIN).is_empty());
+    }
+
+    #[test]
+    fn history_paste_target() {
+        let mock = mock();
+        let config = Config::default();
+
+        let session = open_session(mock.as_ref(), &config, None).unwrap();
+        assert_eq!(history_target(mock.as_ref(), &session), Some(TARGET));
+    }
+
+    #[test]
+    fn history_paste_without_target() {
+        let mock = mock();
+
+        // show from tray clears the session
+        let session = Session::default();
+        assert_eq!(history_target(mock.as_ref(), &session), None);
+
+        let config = Config::default();
+        let session = open_session(mock.as_ref(), &config, None).unwrap();
+        mock.state
+            .lock()
+            .unwrap()
+            .windows
+            .retain(|window| window.id != TARGET);
+        assert_eq!(history_target(mock.as_ref(), &session), None);
+    }
+
+    #[test]
+    fn config_rejects_invalid_profile() {
+        assert!(Config::default().validate().is_ok());
+
+        let invalid = [
+            r#"{"app": "slack", "mode": 4}"#,
+            r#"{"app": "slack", "llm": 2}"#,
+            r#"{"app": "slack", "disabled_modes": [0, 9]}"#,
+            r#"{"app": "slack", "insert_method": 4}"#,
+            r#"{"app": "slack", "insert_method": 6}"#,
+        ];
+        for profile in invalid {
+            let config = Config {
+                profiles: vec![serde_json::from_str(profile).unwrap()],
+                ..Default::default()
+            };
+            assert!(config.validate().is_err(), "{}", profile);
+        }
+
+        let config = Config {
+            mode_shortcuts: vec![ModeShortcut {
+                shortcut: "Ctrl+Alt+T".into(),
+                mode: 7,
+            }],
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn query_rejects_invalid_values() {
+        let config = Config::default();
+        let profile: Profile = serde_json::from_str(r#"{"app": "slack", "llm": 5}"#).unwrap();
+        assert!(build_query(&config, &profile, "Hello".into(), MODE_TRANSLATION).is_err());
+        assert!(build_query(&config, &Profile::default(), "Hello".into(), 9).is_err());
+
+        let profile: Profile =
+            serde_json::from_str(r#"{"app": "slack", "disabled_modes": [1]}"#).unwrap();
+        assert!(build_query(&config, &profile, "Hello".into(), MODE_POLISHING).is_err());
+
+        let query = build_query(&config, &profile, "Hello".into(), MODE_TRANSLATION).unwrap();
+        assert_eq!(query.llm, LLM_CHATGPT);
+        assert_eq!(query.language, "English");
+    }
+
+    #[test]
+    fn language_pair_short_input() {
+        let config = Config {
+            language: "English".into(),
+            secondary_language: "German".into(),
+            ..Default::default()
+        };
+        let profile = Profile::default();
+
+        let mut query = build_query(
+            &config,
+            &profile,
+            "Thanks, see you tomorrow!".into(),
+            MODE_TRANSLATION,
+        )
+        .unwrap();
+        apply_language_pair(&config, &mut query);
+        assert_eq!(query.language, "German");
+
+        let mut query = build_query(
+            &config,
+            &profile,
+            "Ich bin müde heute abend".into(),
+            MODE_TRANSLATION,
+        )
+        .unwrap();
+        apply_language_pair(&config, &mut query);
+        assert_eq!(query.language, "English");
+
+        let mut query = build_query(&config, &profile, "Hello".into(), MODE_POLISHING).unwrap();
+        apply_language_pair(&config, &mut query);
+        assert_eq!(query.language, "English");
+    }
+
+    fn shortcut(text: &str) -> tauri_plugin_global_shortcut::Shortcut {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn shortcuts_replace_previous() {
+        let previous = vec![
+            (shortcut("Ctrl+Space"), ShortcutAction::Pop(None)),
+            (shortcut("Ctrl+Alt+U"), ShortcutAction::Undo),
+        ];
+        let bindings = vec![
+            ("Alt+Space".into(), ShortcutAction::Pop(None)),
+            ("Ctrl+Alt+U".into(), ShortcutAction::Undo),
+        ];
+        let mut registered = vec![];
+        let (shortcuts, released, errors) = update_shortcuts(&previous, bindings, |shortcut| {
+            registered.push(shortcut);
+            Ok(())
+        });
+
+        // unchanged shortcut is not registered twice
+        assert_eq!(registered, vec![shortcut("Alt+Space")]);
+        assert_eq!(released, vec![shortcut("Ctrl+Space")]);
+        assert_eq!(
+            shortcuts,
+            vec![
+                (shortcut("Alt+Space"), ShortcutAction::Pop(None)),
+                (shortcut("Ctrl+Alt+U"), ShortcutAction::Undo),
+            ]
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn shortcuts_keep_previous_on_failure() {
+        let previous = vec![(shortcut("Ctrl+Space"), ShortcutAction::Pop(None))];
+
+        let bindings = vec![("Ctrl+Nope".into(), ShortcutAction::Pop(None))];
+        let (shortcuts, released, errors) = update_shortcuts(&previous, bindings, |_| Ok(()));
+        assert_eq!(shortcuts, previous);
+        assert!(released.is_empty());
+        assert_eq!(errors.len(), 2);
+
+        // taken by another application
+        let bindings = vec![("Alt+Space".into(), ShortcutAction::Pop(None))];
+        let (shortcuts, released, errors) = update_shortcuts(&previous, bindings, |_| {
+            Err(anyhow::anyhow!("already registered"))
+        });
+        assert_eq!(shortcuts, previous);
+        assert!(released.is_empty());
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn token_per_llm() {
+        let config = Config {
+            chatgpt_token: "sk-openai".into(),
+            ..Default::default()
+        };
+        assert_eq!(config.llm_token(LLM_CHATGPT).unwrap(), "sk-openai");
+        assert!(config.llm_token(LLM_GROK).is_err());
+        assert!(config.llm_token(2).is_err());
+    }
+
+    #[test]
+    fn token_migration() {
+        let mut config: Config = serde_json::from_str(r#"{"llm": 1, "token": "xai-key"}"#).unwrap();
+        config.migrate_token();
+        assert_eq!(config.grok_token, "xai-key");
+        assert!(config.chatgpt_token.is_empty());
+        assert!(config.token.is_empty());
+        let saved = serde_json::to_value(&config).unwrap();
+        assert!(saved.get("token").is_none());
+    }
+
+    #[test]
+    fn profile_match_process() {
+        let info = app_info("slack.exe", "Chrome_WidgetWin_1");
+        assert!(profile("slack.exe").is_match(&info));
+        assert!(profile("Slack.EXE").is_match(&info));
+        assert!(profile("  slack.exe ").is_match(&info));
+        assert!(!profile("slack").is_match(&info));
+    }
+
+    #[test]
+    fn profile_match_class() {
+        let info = app_info("firefox", "Navigator");
+        assert!(profile("navigator").is_match(&info));
+        assert!(!profile("chrome").is_match(&info));
+    }
+
+    #[test]
+    fn profile_select_first() {
+        let mut first = profile("slack");
+        first.language = Some("Japanese".into());
+        let mut second = profile("Slack");
+        second.language = Some("German".into());
+        let profiles = [profile("discord"), first, second];
+
+        let selected = select_profile(&profiles, &app_info("slack", "slack"));
+        assert_eq!(
+            selected.and_then(|profile| profile.language),
+            Some("Japanese".into())
+        );
+        assert!(select_profile(&profiles, &app_info("code", "Code")).is_none());
+    }
+
+    #[test]
+    fn profile_match_empty() {
+        assert!(!profile("").is_match(&app_info("", "")));
+        assert!(!profile("   ").is_match(&app_info("slack", "slack")));
+    }
+}
@@ -0,0 +1,364 @@
+// byte range of text and the whitespace following it
+#[derive(Debug, Clone, Copy)]
+struct Unit {
+    start: usize,
+    end: usize,
+    next: usize,
+}
+
+// chunks of at most max_len characters, split at paragraphs, sentences and then words
+// each chunk comes with the whitespace after it, so joining them restores the input
+pub fn split(input: &str, max_len: usize) -> Vec<(&str, &str)> {
+    let mut units = vec![];
+    for paragraph in split_paragraphs(input) {
+        if input[paragraph.start..paragraph.end].chars().count() <= max_len {
+            units.push(paragraph);
+            continue;
+        }
+        for sentence in split_sentences(input, paragraph) {
+            if max_len > 0 && input[sentence.start..sentence.end].chars().count() > max_len {
+                units.extend(split_words(input, sentence, max_len));
+            } else {
+                units.push(sentence);
+            }
+        }
+    }
+
+    // join short units back into a chunk as long as it fits
+    let mut chunks = vec![];
+    let mut current: Option<Unit> = None;
+    let mut len = 0;
+    for unit in units {
+        let unit_len = input[unit.start..unit.next].chars().count();
+        match current.as_mut() {
+            Some(chunk) if len + unit_len <= max_len => {
+                chunk.end = unit.end;
+                chunk.next = unit.next;
+                len += unit_len;
+            }
+            _ => {
+                chunks.extend(current.replace(unit));
+                len = unit_len;
+            }
+        }
+    }
+    chunks.extend(current);
+
+    chunks
+        .into_iter()
+        .map(|unit| (&input[unit.start..unit.end], &input[unit.end..unit.next]))
+        .collect()
+}
+
+// every sentence is a chunk, since no units are joined within zero characters
+pub fn sentences(input: &str) -> Vec<(&str, &str)> {
+    split(input, 0)
+}
+
+// paragraphs are separated by blank lines
+fn split_paragraphs(input: &str) -> Vec<Unit> {
+    let mut units = vec![];
+    let mut start = 0;
+    let mut run = None;
+    for (index, c) in input.char_indices() {
+        if c.is_whitespace() {
+            run.get_or_insert(index);
+            continue;
+        }
+        if let Some(run_start) = run.take() {
+            if run_start > start && input[run_start..index].matches('\n').count() >= 2 {
+                units.push(Unit {
+                    start,
+                    end: run_start,
+                    next: index,
+                });
+                start = index;
+            }
+        }
+    }
+    units.push(Unit {
+        start,
+        end: run.unwrap_or(input.len()),
+        next: input.len(),
+    });
+    units
+}
+
+// sentences end with a terminator and whitespace, cjk terminators need no whitespace
+fn split_sentences(input: &str, paragraph: Unit) -> Vec<Unit> {
+    let mut units = vec![];
+    let mut start = paragraph.start;
+    let mut prev = None;
+    let mut run = None;
+    for (index, c) in input[paragraph.start..paragraph.end].char_indices() {
+        let index = paragraph.start + index;
+        if c.is_whitespace() {
+            run.get_or_insert(index);
+            continue;
+        }
+        if let Some(run_start) = run.take() {
+            if prev.is_some_and(is_terminator) && !is_continued(&input[start..run_start], c) {
+                units.push(Unit {
+                    start,
+                    end: run_start,
+                    next: index,
+                });
+                start = index;
+            }
+        } else if prev.is_some_and(is_cjk_terminator) && !is_closing(c) {
+            units.push(Unit {
+                start,
+                end: index,
+                next: index,
+            });
+            start = index;
+        }
+
+        // closing quotes belong to the sentence before
+        if !(is_closing(c) && prev.is_some_and(is_terminator)) {
+            prev = Some(c);
+        }
+    }
+    units.push(Unit {
+        start,
+        end: paragraph.end,
+        next: paragraph.next,
+    });
+    units
+}
+
+// a sentence over the limit is cut at the last whitespace, or anywhere without whitespace
+fn split_words(input: &str, sentence: Unit, max_len: usize) -> Vec<Unit> {
+    let mut units = vec![];
+    let mut start = sentence.start;
+    loop {
+        let text = &input[start..sentence.end];
+        let Some((limit, _)) = text.char_indices().nth(max_len) else {
+            break;
+        };
+
+        let space = match text[limit..].starts_with(char::is_whitespace) {
+            true => Some(limit),
+            false => text[..limit].rfind(char::is_whitespace),
+        };
+        let (end, next) = match space {
+            Some(space) if !text[..space].trim_end().is_empty() => {
+                let rest = &text[space..];
+                let end = text[..space].trim_end().len();
+                (end, space + rest.len() - rest.trim_start().len())
+            }
+            _ => (limit, limit),
+        };
+        units.push(Unit {
+            start,
+            end: start + end,
+            next: start + next,
+        });
+        start += next;
+    }
+    units.push(Unit {
+        start,
+        end: sentence.end,
+        next: sentence.next,
+    });
+    units
+}
+
+const ABBREVIATIONS: [&str; 24] = [
+    "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "mt", "vs", "etc", "no", "vol", "fig",
+    "inc", "ltd", "co", "corp", "jan", "feb", "aug", "sept", "oct", "dec",
+];
+
+// period of "Dr. Smith", "e.g. apples" or "J. Smith" does not end the sentence
+fn is_continued(sentence: &str, next: char) -> bool {
+    let sentence = sentence.trim_end_matches(is_closing);
+    if !sentence.ends_with('.') {
+        return false;
+    }
+    if next.is_lowercase() || next.is_ascii_digit() {
+        return true;
+    }
+
+    let word = sentence
+        .rsplit(char::is_whitespace)
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches(|c: char| !c.is_alphanumeric())
+        .trim_end_matches('.');
+    let is_initial = word.chars().count() == 1 && word.chars().all(char::is_alphabetic);
+    is_initial
+        || word.contains('.')
+        || ABBREVIATIONS
+            .iter()
+            .any(|abbreviation| word.eq_ignore_ascii_case(abbreviation))
+}
+
+fn is_terminator(c: char) -> bool {
+    matches!(c, '.' | '!' | '?' | '\u{2026}') || is_cjk_terminator(c)
+}
+
+fn is_cjk_terminator(c: char) -> bool {
+    matches!(c, '\u{3002}' | '\u{ff01}' | '\u{ff1f}')
+}
+
+fn is_closing(c: char) -> bool {
+    matches!(
+        c,
+        '"' | '\'' | ')' | '\u{2019}' | '\u{201d}' | '\u{300d}' | '\u{300f}' | '\u{ff09}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts<'a>(chunks: &[(&'a str, &'a str)]) -> Vec<&'a str> {
+        chunks.iter().map(|(text, _)| *text).collect()
+    }
+
+    fn join(chunks: &[(&str, &str)]) -> String {
+        chunks
+            .iter()
+            .map(|(text, separator)| format!("{}{}", text, separator))
+            .collect()
+    }
+
+    #[test]
+    fn split_restores_input() {
+        let inputs = [
+            "",
+            "single",
+            "  leading and trailing  ",
+            "Para one. Second sentence!\n\nPara two is here.\n\n\nPara three? Yes.  ",
+            "短い文。次の文です。「引用。」終わり！",
+            "line one\nline two\r\n\r\nline three",
+            "word   another\t longerword  end of the sentence",
+        ];
+        for input in inputs {
+            for max_len in [0, 5, 20, 1000] {
+                assert_eq!(
+                    join(&split(input, max_len)),
+                    input,
+                    "{:?} {}",
+                    input,
+                    max_len
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn split_paragraphs_first() {
+        let input = "Para one. Second sentence!\n\nPara two is here.";
+        let chunks = split(input, 30);
+        assert_eq!(
+            texts(&chunks),
+            ["Para one. Second sentence!", "Para two is here."]
+        );
+        assert_eq!(chunks[0].1, "\n\n");
+    }
+
+    #[test]
+    fn split_short_input() {
+        let input = "Para one.\n\nPara two.";
+        assert_eq!(texts(&split(input, 1000)), [input]);
+    }
+
+    #[test]
+    fn split_long_paragraph() {
+        let input = "First sentence here. Second sentence here. Third one.";
+        assert_eq!(
+            texts(&split(input, 45)),
+            ["First sentence here. Second sentence here.", "Third one."]
+        );
+    }
+
+    #[test]
+    fn split_long_sentence() {
+        let input = "This single sentence is much longer than the limit of chunks. Next.";
+        let chunks = split(input, 20);
+        assert!(chunks.iter().all(|(text, _)| text.chars().count() <= 20));
+        assert_eq!(
+            texts(&chunks),
+            [
+                "This single sentence",
+                "is much longer than",
+                "the limit of chunks.",
+                "Next."
+            ]
+        );
+        assert_eq!(join(&chunks), input);
+    }
+
+    #[test]
+    fn split_long_sentence_without_whitespace() {
+        let input = "これはとても長い文なので途中で切る必要があります。";
+        let chunks = split(input, 10);
+        assert_eq!(
+            texts(&chunks),
+            ["これはとても長い文な", "ので途中で切る必要が", "あります。"]
+        );
+        assert_eq!(join(&chunks), input);
+
+        // sentences are never cut into words
+        assert_eq!(texts(&sentences(input)), [input]);
+    }
+
+    #[test]
+    fn sentences_latin() {
+        let input = "He said \"Hi.\" Then left. ok";
+        assert_eq!(
+            texts(&sentences(input)),
+            ["He said \"Hi.\"", "Then left. ok"]
+        );
+    }
+
+    #[test]
+    fn sentences_cjk() {
+        let input = "短い文。次の文です。「引用。」終わり！";
+        assert_eq!(
+            texts(&sentences(input)),
+            ["短い文。", "次の文です。", "「引用。」", "終わり！"]
+        );
+    }
+
+    #[test]
+    fn sentences_abbreviation() {
+        assert_eq!(
+            texts(&sentences("Dr. Smith went home. He slept.")),
+            ["Dr. Smith went home.", "He slept."]
+        );
+        assert_eq!(
+            texts(&sentences("Buy fruit, e.g. Apples and pears. Then rest.")),
+            ["Buy fruit, e.g. Apples and pears.", "Then rest."]
+        );
+        assert_eq!(
+            texts(&sentences("J. R. R. Tolkien wrote it. It is long.")),
+            ["J. R. R. Tolkien wrote it.", "It is long."]
+        );
+        assert_eq!(
+            texts(&sentences("Apples vs. Oranges etc. are fruits. Yes.")),
+            ["Apples vs. Oranges etc. are fruits.", "Yes."]
+        );
+    }
+
+    #[test]
+    fn sentences_lowercase_continues() {
+        assert_eq!(
+            texts(&sentences("It costs approx. ten dollars. Fine.")),
+            ["It costs approx. ten dollars.", "Fine."]
+        );
+        assert_eq!(
+            texts(&sentences("See page no. 5 for details. Thanks!")),
+            ["See page no. 5 for details.", "Thanks!"]
+        );
+    }
+
+    #[test]
+    fn sentences_other_terminators() {
+        assert_eq!(
+            texts(&sentences("Really? Yes! Dr. No.")),
+            ["Really?", "Yes!", "Dr. No."]
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use crate::*;
+
+const MODES: [(&str, usize); 4] = [
+    ("translate", app::MODE_TRANSLATION),
+    ("polish", app::MODE_POLISHING),
+    ("complete", app::MODE_COMPLETION),
+    ("explain", app::MODE_EXPLANATION),
+];
+
+const LANGUAGES: [(&str, &str); 14] = [
+    ("ar", "Arabic"),
+    ("de", "German"),
+    ("en", "English"),
+    ("es", "Spanish"),
+    ("fr", "French"),
+    ("hi", "Hindi"),
+    ("it", "Italian"),
+    ("ja", "Japanese"),
+    ("ko", "Korean"),
+    ("nl", "Dutch"),
+    ("pl", "Polish"),
+    ("pt", "Portuguese"),
+    ("ru", "Russian"),
+    ("zh", "Chinese"),
+];
+
+const STYLES: [&str; 8] = [
+    "formal",
+    "casual",
+    "polite",
+    "friendly",
+    "concise",
+    "academic",
+    "business",
+    "technical",
+];
+
+// per-request override given by prefixes such as "/ja", "@de" or "/polish formal"
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Command {
+    pub mode: Option<usize>,
+    pub language: Option<String>,
+    pub style: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Suggestion {
+    pub text: String,
+    pub description: String,
+}
+
+// consume leading commands, the rest is the text to send
+pub fn parse(input: &str) -> (Command, &str) {
+    let mut command = Command::default();
+    let mut rest = input.trim_start();
+
+    while let Some((token, next)) = split_token(rest) {
+        let name = token.to_lowercase();
+        if let Some(name) = name.strip_prefix('/') {
+            if let Some(mode) = find_mode(name) {
+                command.mode = Some(mode);
+                rest = next;
+
+                // "/polish formal" takes the style as argument
+                if let Some((argument, next)) = split_token(rest) {
+                    if let Some(style) = find_style(&argument.to_lowercase()) {
+                        command.style = Some(style.into());
+                        rest = next;
+                    }
+                }
+                continue;
+            }
+            if let Some(style) = find_style(name) {
+                command.style = Some(style.into());
+                rest = next;
+                continue;
+            }
+        }
+        if let Some(name) = name.strip_prefix(['/', '@']) {
+            if let Some(language) = find_language(name) {
+                command.language = Some(language.into());
+                rest = next;
+                continue;
+            }
+        }
+        break;
+    }
+
+    (command, rest)
+}
+
+// candidates for the command being typed at the end of input
+pub fn suggest(input: &str) -> Vec<Suggestion> {
+    let Some(token) = input.split_whitespace().last() else {
+        return vec![];
+    };
+    if input.ends_with(char::is_whitespace) {
+        return vec![];
+    }
+
+    // commands are accepted only at the beginning of input
+    let (_, rest) = parse(&input[..input.len() - token.len()]);
+    if !rest.is_empty() {
+        return vec![];
+    }
+
+    let token = token.to_lowercase();
+    let mut suggestions = vec![];
+    if let Some(prefix) = token.strip_prefix('/') {
+        for (name, _) in MODES {
+            if name.starts_with(prefix) {
+                suggestions.push(Suggestion {
+                    text: format!("/{}", name),
+                    description: "mode".into(),
+                });
+            }
+        }
+        for name in STYLES {
+            if name.starts_with(prefix) {
+                suggestions.push(Suggestion {
+                    text: format!("/{}", name),
+                    description: "style".into(),
+                });
+            }
+        }
+    }
+    if let Some(prefix) = token.strip_prefix(['/', '@']) {
+        for (code, language) in LANGUAGES {
+            if code.starts_with(prefix) || language.to_lowercase().starts_with(prefix) {
+                suggestions.push(Suggestion {
+                    text: format!("{}{}", &token[..1], code),
+                    description: language.into(),
+                });
+            }
+        }
+    }
+    suggestions
+}
+
+fn split_token(input: &str) -> Option<(&str, &str)> {
+    let input = input.trim_start();
+    if input.is_empty() {
+        return None;
+    }
+    let (token, rest) = input.split_at(input.find(char::is_whitespace).unwrap_or(input.len()));
+    Some((token, rest.trim_start()))
+}
+
+fn find_mode(name: &str) -> Option<usize> {
+    MODES
+        .iter()
+        .find(|(other, _)| *other == name)
+        .map(|(_, mode)| *mode)
+}
+
+fn find_language(name: &str) -> Option<&'static str> {
+    LANGUAGES
+        .iter()
+        .find(|(code, language)| *code == name || language.eq_ignore_ascii_case(name))
+        .map(|(_, language)| *language)
+}
+
+fn find_style(name: &str) -> Option<&'static str> {
+    STYLES.iter().find(|other| **other == name).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(suggestions: &[Suggestion]) -> Vec<(&str, &str)> {
+        suggestions
+            .iter()
+            .map(|suggestion| (suggestion.text.as_str(), suggestion.description.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_language() {
+        let (command, rest) = parse("/ja Hello");
+        assert_eq!(command.language.as_deref(), Some("Japanese"));
+        assert_eq!(command.mode, None);
+        assert_eq!(rest, "Hello");
+
+        let (command, rest) = parse("@DE  Good morning");
+        assert_eq!(command.language.as_deref(), Some("German"));
+        assert_eq!(rest, "Good morning");
+    }
+
+    #[test]
+    fn parse_mode_and_style() {
+        let (command, rest) = parse("/polish formal hey there");
+        assert_eq!(command.mode, Some(app::MODE_POLISHING));
+        assert_eq!(command.style.as_deref(), Some("formal"));
+        assert_eq!(rest, "hey there");
+
+        let (command, rest) = parse("/explain /casual @fr I goed home");
+        assert_eq!(command.mode, Some(app::MODE_EXPLANATION));
+        assert_eq!(command.style.as_deref(), Some("casual"));
+        assert_eq!(command.language.as_deref(), Some("French"));
+        assert_eq!(rest, "I goed home");
+    }
+
+    // "/polish" is the mode, the language is given by "@polish" or "/pl"
+    #[test]
+    fn parse_polish_ambiguity() {
+        let (command, _) = parse("/polish text");
+        assert_eq!(command.mode, Some(app::MODE_POLISHING));
+        assert_eq!(command.language, None);
+
+        let (command, _) = parse("@polish text");
+        assert_eq!(command.mode, None);
+        assert_eq!(command.language.as_deref(), Some("Polish"));
+
+        let (command, _) = parse("/pl text");
+        assert_eq!(command.language.as_deref(), Some("Polish"));
+    }
+
+    #[test]
+    fn parse_plain_text() {
+        let (command, rest) = parse("Hello /ja");
+        assert_eq!(command, Command::default());
+        assert_eq!(rest, "Hello /ja");
+
+        let (command, rest) = parse("/unknown text");
+        assert_eq!(command, Command::default());
+        assert_eq!(rest, "/unknown text");
+    }
+
+    #[test]
+    fn parse_bare_command() {
+        let (command, rest) = parse("/ja");
+        assert_eq!(command.language.as_deref(), Some("Japanese"));
+        assert!(rest.is_empty());
+
+        let (_, rest) = parse("/polish formal  ");
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn suggest_polish_both() {
+        let suggestions = suggest("/pol");
+        assert_eq!(
+            texts(&suggestions),
+            vec![("/polish", "mode"), ("/polite", "style"), ("/pl", "Polish")]
+        );
+
+        let suggestions = suggest("/polish");
+        assert_eq!(
+            texts(&suggestions),
+            vec![("/polish", "mode"), ("/pl", "Polish")]
+        );
+
+        let suggestions = suggest("@pol");
+        assert_eq!(texts(&suggestions), vec![("@pl", "Polish")]);
+    }
+
+    #[test]
+    fn suggest_after_commands() {
+        assert_eq!(texts(&suggest("/polish /for")), vec![("/formal", "style")]);
+        assert_eq!(
+            texts(&suggest("/ja @d")),
+            vec![("@de", "German"), ("@nl", "Dutch")]
+        );
+        assert!(suggest("Hello /ja").is_empty());
+        assert!(suggest("/ja ").is_empty());
+        assert!(suggest("").is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+// unchanged text has the same before and after
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Hunk {
+    pub changed: bool,
+    pub before: String,
+    pub after: String,
+}
+
+// lcs table grows by the product of token counts, 8 MB at most
+// the shorter side is within 2048 tokens, so u16 lengths never overflow
+const MAX_TABLE_SIZE: usize = 1 << 22;
+
+// word-level diff, each cjk character counts as a word
+pub fn diff(before: &str, after: &str) -> Vec<Hunk> {
+    let before = tokenize(before);
+    let after = tokenize(after);
+
+    // edits while typing are local, so only the middle needs the table
+    let prefix = before
+        .iter()
+        .zip(&after)
+        .take_while(|(before, after)| before == after)
+        .count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(before, after)| before == after)
+        .count();
+
+    let mut hunks = vec![];
+    push_hunk(
+        &mut hunks,
+        false,
+        &before[..prefix].concat(),
+        &after[..prefix].concat(),
+    );
+    diff_tokens(
+        &mut hunks,
+        &before[prefix..before.len() - suffix],
+        &after[prefix..after.len() - suffix],
+    );
+    push_hunk(
+        &mut hunks,
+        false,
+        &before[before.len() - suffix..].concat(),
+        &after[after.len() - suffix..].concat(),
+    );
+    hunks
+}
+
+// adjacent hunks of the same kind are merged, empty ones are skipped
+fn push_hunk(hunks: &mut Vec<Hunk>, changed: bool, before: &str, after: &str) {
+    if before.is_empty() && after.is_empty() {
+        return;
+    }
+    match hunks.last_mut() {
+        Some(hunk) if hunk.changed == changed => {
+            hunk.before.push_str(before);
+            hunk.after.push_str(after);
+        }
+        _ => hunks.push(Hunk {
+            changed,
+            before: before.into(),
+            after: after.into(),
+        }),
+    }
+}
+
+fn diff_tokens(hunks: &mut Vec<Hunk>, before: &[&str], after: &[&str]) {
+    if before.len() * after.len() > MAX_TABLE_SIZE {
+        push_hunk(hunks, true, &before.concat(), &after.concat());
+        return;
+    }
+
+    // lengths of lcs of suffixes
+    let width = after.len() + 1;
+    let mut table = vec![0u16; (before.len() + 1) * width];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            table[i * width + j] = if before[i] == after[j] {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                table[(i + 1) * width + j].max(table[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() || j < after.len() {
+        if i < before.len() && j < after.len() && before[i] == after[j] {
+            push_hunk(hunks, false, before[i], after[j]);
+            i += 1;
+            j += 1;
+        } else if j < after.len()
+            && (i == before.len() || table[i * width + j + 1] >= table[(i + 1) * width + j])
+        {
+            push_hunk(hunks, true, "", after[j]);
+            j += 1;
+        } else {
+            push_hunk(hunks, true, before[i], "");
+            i += 1;
+        }
+    }
+}
+
+// runs of letters, runs of whitespace, or single other characters
+fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut start = 0;
+    let mut kind = None;
+    for (index, c) in text.char_indices() {
+        let next = if is_cjk(c) {
+            None
+        } else if c.is_alphanumeric() {
+            Some(0)
+        } else if c.is_whitespace() {
+            Some(1)
+        } else {
+            None
+        };
+        if index > start && (next.is_none() || next != kind) {
+            tokens.push(&text[start..index]);
+            start = index;
+        }
+        kind = next;
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32, 0x2e80..=0x9fff | 0xac00..=0xd7af | 0xf900..=0xfaff | 0xff00..=0xffef)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn concat(hunks: &[Hunk]) -> (String, String) {
+        let before = hunks.iter().map(|hunk| hunk.before.as_str()).collect();
+        let after = hunks.iter().map(|hunk| hunk.after.as_str()).collect();
+        (before, after)
+    }
+
+    fn changes(hunks: &[Hunk]) -> Vec<(&str, &str)> {
+        hunks
+            .iter()
+            .filter(|hunk| hunk.changed)
+            .map(|hunk| (hunk.before.as_str(), hunk.after.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn diff_restores_texts() {
+        let cases = [
+            ("I has a apple.", "I have an apple."),
+            ("Hello  world\n\nBye", "Hello world\nBye!"),
+            ("今日は晴れです。", "今日は雨です。"),
+            ("abc", "xyz"),
+            ("", "new text"),
+            ("old text", ""),
+        ];
+        for (before, after) in cases {
+            let hunks = diff(before, after);
+            assert_eq!(concat(&hunks), (before.into(), after.into()));
+            assert!(hunks
+                .iter()
+                .all(|hunk| !hunk.before.is_empty() || !hunk.after.is_empty()));
+        }
+    }
+
+    #[test]
+    fn diff_words() {
+        let hunks = diff("I has a apple.", "I have an apple.");
+        assert_eq!(changes(&hunks), vec![("has", "have"), ("a", "an")]);
+    }
+
+    #[test]
+    fn diff_identical_and_empty() {
+        let hunks = diff("same text", "same text");
+        assert_eq!(hunks.len(), 1);
+        assert!(!hunks[0].changed);
+        assert!(diff("", "").is_empty());
+    }
+
+    #[test]
+    fn tokenize_cjk_and_whitespace() {
+        assert_eq!(tokenize("今日は晴れ"), vec!["今", "日", "は", "晴", "れ"]);
+        assert_eq!(tokenize("a  b\n"), vec!["a", "  ", "b", "\n"]);
+        assert_eq!(tokenize("don't!"), vec!["don", "'", "t", "!"]);
+        assert_eq!(tokenize("漢字abc"), vec!["漢", "字", "abc"]);
+
+        let hunks = diff("今日は晴れです", "今日は雨です");
+        assert_eq!(changes(&hunks), vec![("晴れ", "雨")]);
+    }
+
+    #[test]
+    fn diff_large_fallback() {
+        let before = format!("Start {}end", "a ".repeat(1500));
+        let after = format!("Start {}end", "b ".repeat(1500));
+        assert!(tokenize(&before).len() * tokenize(&after).len() > MAX_TABLE_SIZE);
+
+        let hunks = diff(&before, &after);
+        assert_eq!(concat(&hunks), (before, after));
+        // common prefix and suffix are kept out of the fallback hunk
+        assert_eq!(hunks.len(), 3);
+        assert_eq!(hunks[0].before, "Start ");
+        assert!(hunks[1].changed);
+        assert_eq!(hunks[2].before, " end");
+    }
+}
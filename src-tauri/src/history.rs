@@ -0,0 +1,158 @@
+use crate::*;
+
+// confirmed session of window main
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    pub id: u64,
+    pub timestamp: u64,
+    pub mode: usize,
+    pub language: String,
+    pub llm: usize,
+    pub input: String,
+    pub output: String,
+    pub app: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    filepath: std::path::PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl History {
+    pub fn load(filepath: std::path::PathBuf) -> Self {
+        let entries = std::fs::File::open(&filepath)
+            .ok()
+            .and_then(|rdr| serde_json::from_reader::<_, Vec<Entry>>(rdr).ok())
+            .unwrap_or_default();
+        Self { filepath, entries }
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        if let Some(path) = self.filepath.parent() {
+            std::fs::create_dir_all(path)?;
+        }
+
+        let writer = std::fs::File::create(&self.filepath)?;
+        serde_json::to_writer(writer, &self.entries)?;
+        log::info!("save history {:?}", self.filepath);
+        Ok(())
+    }
+
+    pub fn push(&mut self, query: &app::Query, output: String, app: String) {
+        let id = self
+            .entries
+            .iter()
+            .map(|entry| entry.id + 1)
+            .max()
+            .unwrap_or(0);
+        let entry = Entry {
+            id,
+            timestamp: timestamp(),
+            mode: query.mode,
+            language: query.language.clone(),
+            llm: query.llm,
+            input: query.input.clone(),
+            output,
+            app,
+        };
+        self.entries.push(entry);
+    }
+
+    // drop entries beyond the count or older than the days, zero means no limit
+    pub fn retain(&mut self, size: usize, days: u64) {
+        if days > 0 {
+            let min_timestamp = timestamp().saturating_sub(days.saturating_mul(24 * 60 * 60));
+            self.entries
+                .retain(|entry| entry.timestamp >= min_timestamp);
+        }
+        if size > 0 && self.entries.len() > size {
+            self.entries.drain(..self.entries.len() - size);
+        }
+    }
+
+    // newest first, case-insensitive match against input, output and app
+    pub fn search(&self, text: &str) -> Vec<Entry> {
+        let text = text.to_lowercase();
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| {
+                entry.input.to_lowercase().contains(&text)
+                    || entry.output.to_lowercase().contains(&text)
+                    || entry.app.to_lowercase().contains(&text)
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.entries.retain(|entry| entry.id != id);
+    }
+}
+
+fn timestamp() -> u64 {
+    let duration = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    duration.as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(timestamps: &[u64]) -> History {
+        let entries = timestamps
+            .iter()
+            .enumerate()
+            .map(|(id, timestamp)| Entry {
+                id: id as u64,
+                timestamp: *timestamp,
+                mode: 0,
+                language: "English".into(),
+                llm: 0,
+                input: format!("input {}", id),
+                output: format!("output {}", id),
+                app: "editor".into(),
+            })
+            .collect();
+        History {
+            filepath: Default::default(),
+            entries,
+        }
+    }
+
+    fn ids(history: &History) -> Vec<u64> {
+        history.entries.iter().map(|entry| entry.id).collect()
+    }
+
+    #[test]
+    fn retain_days() {
+        let now = timestamp();
+        let mut history = history(&[now - 3 * 24 * 60 * 60, now - 60, now]);
+        history.retain(0, 1);
+        assert_eq!(ids(&history), vec![1, 2]);
+    }
+
+    #[test]
+    fn retain_large_days() {
+        let mut history = history(&[0, timestamp()]);
+        history.retain(0, u64::MAX);
+        assert_eq!(ids(&history), vec![0, 1]);
+    }
+
+    #[test]
+    fn retain_size() {
+        let now = timestamp();
+        let mut history = history(&[now, now, now]);
+        history.retain(2, 0);
+        assert_eq!(ids(&history), vec![1, 2]);
+        history.retain(0, 0);
+        assert_eq!(ids(&history), vec![1, 2]);
+    }
+}
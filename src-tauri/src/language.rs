@@ -0,0 +1,159 @@
+// local language identification, nothing is sent over network
+pub fn detect(text: &str) -> Option<String> {
+    let info = whatlang::detect(text)?;
+    if !info.is_reliable() {
+        return None;
+    }
+
+    Some(name(info.lang()).into())
+}
+
+// unreliable guess, e.g. of short text
+pub fn guess(text: &str) -> Option<String> {
+    let info = whatlang::detect(text)?;
+    Some(name(info.lang()).into())
+}
+
+// short text is unreliable among all languages, but choosing between a few is not
+// index of the language the text is most likely written in
+pub fn closest(text: &str, languages: &[&str]) -> Option<usize> {
+    let langs = languages
+        .iter()
+        .map(|language| lang(language))
+        .collect::<Option<Vec<_>>>()?;
+
+    // text reliably in another language is in none of them
+    let info = whatlang::detect(text)?;
+    if info.is_reliable() && !langs.contains(&info.lang()) {
+        return None;
+    }
+
+    let info = whatlang::Detector::with_allowlist(langs.clone()).detect(text)?;
+    langs.iter().position(|lang| *lang == info.lang())
+}
+
+// unreliable guess is enough here, since texts in different scripts never match
+pub fn is_same_text(text: &str, other: &str) -> bool {
+    match (whatlang::detect(text), whatlang::detect(other)) {
+        (Some(info), Some(other_info)) => info.lang() == other_info.lang(),
+        _ => false,
+    }
+}
+
+// configured languages are free text such as "english" or "Chinese (Traditional)"
+// words are compared as a whole, "Malayalam" is not "Malay"
+pub fn is_same(language: &str, detected: &str) -> bool {
+    let language = words(language);
+    let detected = words(detected);
+    !detected.is_empty()
+        && language
+            .windows(detected.len())
+            .any(|window| window == detected.as_slice())
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+// languages outside of detection, e.g. codes or dialects, are never compared
+pub fn is_known(language: &str) -> bool {
+    lang(language).is_some()
+}
+
+fn lang(language: &str) -> Option<whatlang::Lang> {
+    whatlang::Lang::all()
+        .iter()
+        .copied()
+        .find(|lang| is_same(language, name(*lang)))
+}
+
+fn name(lang: whatlang::Lang) -> &'static str {
+    match lang {
+        whatlang::Lang::Cmn => "Chinese",
+        lang => lang.eng_name(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_language() {
+        assert!(is_same("English", "English"));
+        assert!(is_same(" english ", "English"));
+        assert!(is_same("Chinese (Traditional)", "Chinese"));
+        assert!(is_same("British English", "English"));
+    }
+
+    #[test]
+    fn different_language() {
+        assert!(!is_same("Malayalam", "Malay"));
+        assert!(!is_same("Malay", "Malayalam"));
+        assert!(!is_same("Englishman", "English"));
+        assert!(!is_same("", "English"));
+        assert!(!is_same("English", ""));
+    }
+
+    #[test]
+    fn known_language() {
+        assert!(is_known("Japanese"));
+        assert!(is_known("Chinese (Simplified)"));
+        assert!(is_known("Malayalam"));
+        assert!(!is_known("ja"));
+        assert!(!is_known("Klingon"));
+    }
+
+    #[test]
+    fn detect_language() {
+        assert_eq!(
+            detect("申し訳ありませんが、明日の会議には参加できません。").as_deref(),
+            Some("Japanese")
+        );
+        assert_eq!(
+            detect(
+                "I'm sorry, but I cannot explain the weather as I am only a translation engine."
+            )
+            .as_deref(),
+            Some("English")
+        );
+        assert_eq!(detect("").as_deref(), None);
+    }
+
+    #[test]
+    fn closest_short_sentences() {
+        let pair = ["English", "German"];
+        assert_eq!(closest("Hello, how are you doing today?", &pair), Some(0));
+        assert_eq!(closest("Ich bin müde heute abend", &pair), Some(1));
+        assert_eq!(
+            closest(
+                "Hello, I would like to book a table for two people tonight.",
+                &pair
+            ),
+            Some(0)
+        );
+        assert_eq!(closest("Thanks, see you tomorrow!", &pair), Some(0));
+        assert_eq!(closest("Danke, bis morgen!", &pair), Some(1));
+
+        let pair = ["English", "Japanese"];
+        assert_eq!(closest("Thanks, see you tomorrow!", &pair), Some(0));
+        assert_eq!(closest("また明日", &pair), Some(1));
+    }
+
+    #[test]
+    fn closest_other_language() {
+        let pair = ["English", "German"];
+        assert_eq!(
+            closest(
+                "Je voudrais réserver une table pour deux personnes ce soir.",
+                &pair
+            ),
+            None
+        );
+        assert_eq!(closest("Hello", &["English", "Klingon"]), None);
+        assert_eq!(closest("", &pair), None);
+    }
+}
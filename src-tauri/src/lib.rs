@@ -1,5 +1,11 @@
 mod app;
+mod chunk;
+mod command;
+mod diff;
+mod history;
+mod language;
 mod llm;
+mod platform;
 
 // enable mobile entry point when mobile target
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -14,13 +20,20 @@ pub fn run() -> anyhow::Result<()> {
     tauri::Builder::default()
         .plugin(app::setup_plugin_clipboard()?)
         .plugin(app::setup_plugin_global_shortcut()?)
+        .plugin(app::setup_plugin_notification()?)
         .setup(app::setup)
         .invoke_handler(tauri::generate_handler![
             app::on_confirm_input,
             app::on_exit_input,
             app::on_change_input,
+            app::on_refine_input,
             app::on_change_config,
             app::on_get_config,
+            app::on_get_shortcut_errors,
+            app::on_get_history,
+            app::on_paste_history,
+            app::on_copy_history,
+            app::on_delete_history,
         ])
         .on_window_event(app::setup_window_event_handle()?)
         .run(tauri::generate_context!())?;
@@ -0,0 +1,205 @@
+use anyhow::Context;
+
+use super::*;
+
+#[derive(Debug, Clone, Default)]
+pub struct MockWindow {
+    pub id: WindowId,
+    pub rect: Rect,
+    pub info: AppInfo,
+    pub caret: Option<Rect>,
+    pub own: bool,
+    // text selected in the window
+    pub selection: String,
+    // text pasted into the window
+    pub pasted: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MockState {
+    pub windows: Vec<MockWindow>,
+    pub foreground: Option<WindowId>,
+    pub clipboard: ClipboardContent,
+    pub primary_selection: Option<String>,
+    // synthesized input is rejected, e.g. by elevated target on windows
+    pub block_input: bool,
+}
+
+// in-memory desktop, records pasted text per window instead of sending keys
+pub struct Mock {
+    pub state: std::sync::Mutex<MockState>,
+}
+
+impl Default for Mock {
+    fn default() -> Self {
+        let window = MockWindow {
+            id: WindowId(1),
+            rect: Rect {
+                left: 0,
+                top: 0,
+                right: 1280,
+                bottom: 720,
+            },
+            info: AppInfo {
+                process: "mock".into(),
+                class: "Mock".into(),
+            },
+            ..Default::default()
+        };
+        let state = MockState {
+            foreground: Some(window.id),
+            windows: vec![window],
+            ..Default::default()
+        };
+        Self::new(state)
+    }
+}
+
+impl Mock {
+    pub fn new(state: MockState) -> Self {
+        let state = std::sync::Mutex::new(state);
+        Self { state }
+    }
+
+    fn with_state<T>(
+        &self,
+        f: impl FnOnce(&mut MockState) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| anyhow::anyhow!("mock state is poisoned"))?;
+        f(&mut state)
+    }
+
+    fn with_window<T>(
+        &self,
+        window: WindowId,
+        f: impl FnOnce(&mut MockWindow) -> T,
+    ) -> anyhow::Result<T> {
+        self.with_state(|state| {
+            let window = state
+                .windows
+                .iter_mut()
+                .find(|other| other.id == window)
+                .context("window is not found")?;
+            Ok(f(window))
+        })
+    }
+
+    fn foreground(&self) -> anyhow::Result<WindowId> {
+        self.with_state(|state| state.foreground.context("foreground window is not found"))
+    }
+
+    fn input_target(&self) -> anyhow::Result<WindowId> {
+        if self.with_state(|state| Ok(state.block_input))? {
+            return Err(anyhow::anyhow!("input is blocked by the target"));
+        }
+        self.foreground()
+    }
+}
+
+impl Platform for Mock {
+    fn get_foreground_window(&self) -> anyhow::Result<WindowId> {
+        self.foreground()
+    }
+
+    fn is_own_window(&self, window: WindowId) -> anyhow::Result<bool> {
+        self.with_window(window, |window| window.own)
+    }
+
+    fn get_window_rect(&self, window: WindowId) -> anyhow::Result<Rect> {
+        self.with_window(window, |window| window.rect)
+    }
+
+    fn get_caret_rect(&self, window: WindowId) -> anyhow::Result<Rect> {
+        self.with_window(window, |window| window.caret)?
+            .context("caret is not found")
+    }
+
+    fn get_work_area(&self, monitor: Rect) -> anyhow::Result<Rect> {
+        Ok(monitor)
+    }
+
+    fn get_app_info(&self, window: WindowId) -> anyhow::Result<AppInfo> {
+        self.with_window(window, |window| window.info.clone())
+    }
+
+    fn focus_window(&self, window: WindowId) -> anyhow::Result<()> {
+        self.with_window(window, |_| ())?;
+        self.with_state(|state| {
+            state.foreground = Some(window);
+            Ok(())
+        })
+    }
+
+    fn read_clipboard(&self) -> anyhow::Result<ClipboardContent> {
+        self.with_state(|state| Ok(state.clipboard.clone()))
+    }
+
+    fn write_clipboard(&self, content: &ClipboardContent) -> anyhow::Result<()> {
+        self.with_state(|state| {
+            state.clipboard = content.clone();
+            Ok(())
+        })
+    }
+
+    fn read_primary_selection(&self) -> anyhow::Result<Option<String>> {
+        self.with_state(|state| Ok(state.primary_selection.clone()))
+    }
+
+    fn send_paste(&self, key: PasteKey) -> anyhow::Result<()> {
+        let window = self.input_target()?;
+        let ClipboardContent::Text(text) = self.read_clipboard()? else {
+            return Ok(());
+        };
+        log::info!("[mock] paste {} into {:?} by {:?}", text, window, key);
+        self.with_window(window, |window| window.pasted.push(text))
+    }
+
+    fn send_copy(&self) -> anyhow::Result<()> {
+        let window = self.foreground()?;
+        let selection = self.with_window(window, |window| window.selection.clone())?;
+        log::info!("[mock] copy {} from {:?}", selection, window);
+        if !selection.is_empty() {
+            self.write_clipboard(&ClipboardContent::Text(selection))?;
+        }
+        Ok(())
+    }
+
+    fn send_undo(&self) -> anyhow::Result<()> {
+        let window = self.foreground()?;
+        log::info!("[mock] undo {:?}", window);
+        self.with_window(window, |window| {
+            window.pasted.pop();
+        })
+    }
+
+    fn send_backspace(&self, count: usize) -> anyhow::Result<()> {
+        let window = self.foreground()?;
+        log::info!("[mock] backspace {} in {:?}", count, window);
+        self.with_window(window, |window| {
+            // editors delete a grapheme per backspace, also beyond the last insertion
+            let mut count = count;
+            while let Some(text) = window.pasted.last_mut() {
+                let graphemes: Vec<&str> =
+                    unicode_segmentation::UnicodeSegmentation::graphemes(text.as_str(), true)
+                        .collect();
+                let len = graphemes.len().saturating_sub(count);
+                count -= graphemes.len() - len;
+                *text = graphemes[..len].concat();
+                if count == 0 || window.pasted.len() == 1 {
+                    break;
+                }
+                window.pasted.pop();
+            }
+        })
+    }
+
+    fn send_text(&self, text: &str, _: std::time::Duration) -> anyhow::Result<()> {
+        let window = self.input_target()?;
+        log::info!("[mock] type {} into {:?}", text, window);
+        let text = text.chars().filter(|c| is_typed(*c)).collect();
+        self.with_window(window, |window| window.pasted.push(text))
+    }
+}
@@ -0,0 +1,207 @@
+mod mock;
+#[cfg(target_os = "linux")]
+mod wayland;
+#[cfg(target_os = "windows")]
+mod win32;
+#[cfg(target_os = "linux")]
+mod x11;
+
+pub use mock::*;
+
+// window handle of the native windowing system
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct WindowId(pub u64);
+
+// physical screen coordinates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AppInfo {
+    pub process: String,
+    pub class: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum ClipboardContent {
+    Text(String),
+    Image(tauri::image::Image<'static>),
+    #[default]
+    Empty,
+}
+
+// key chord which makes the focus window paste clipboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteKey {
+    CtrlV,
+    CtrlShiftV,
+    ShiftInsert,
+}
+
+// windowing, focus and input synthesis of the desktop environment
+pub trait Platform: Send + Sync {
+    fn get_foreground_window(&self) -> anyhow::Result<WindowId>;
+
+    fn is_own_window(&self, window: WindowId) -> anyhow::Result<bool>;
+
+    fn get_window_rect(&self, window: WindowId) -> anyhow::Result<Rect>;
+
+    // text caret of the window in screen coordinates
+    fn get_caret_rect(&self, window: WindowId) -> anyhow::Result<Rect>;
+
+    // monitor area excluding taskbar and panels
+    fn get_work_area(&self, monitor: Rect) -> anyhow::Result<Rect>;
+
+    fn get_app_info(&self, window: WindowId) -> anyhow::Result<AppInfo>;
+
+    fn focus_window(&self, window: WindowId) -> anyhow::Result<()>;
+
+    fn read_clipboard(&self) -> anyhow::Result<ClipboardContent>;
+
+    fn write_clipboard(&self, content: &ClipboardContent) -> anyhow::Result<()>;
+
+    // selected text without explicit copy, e.g. primary selection of X11
+    fn read_primary_selection(&self) -> anyhow::Result<Option<String>>;
+
+    fn send_paste(&self, key: PasteKey) -> anyhow::Result<()>;
+
+    fn send_copy(&self) -> anyhow::Result<()>;
+
+    fn send_undo(&self) -> anyhow::Result<()>;
+
+    fn send_backspace(&self, count: usize) -> anyhow::Result<()>;
+
+    // type text as unicode key events, for targets which can not receive paste
+    fn send_text(&self, text: &str, interval: std::time::Duration) -> anyhow::Result<()>;
+}
+
+// QUICKLIME_PLATFORM=mock runs the app without touching the desktop
+pub fn create(app: &tauri::AppHandle) -> std::sync::Arc<dyn Platform> {
+    if std::env::var("QUICKLIME_PLATFORM").as_deref() == Ok("mock") {
+        log::info!("use platform mock");
+        return std::sync::Arc::new(Mock::default());
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        log::info!("use platform win32");
+        std::sync::Arc::new(win32::Win32::new(app))
+    }
+
+    // prefer wayland even if the app runs on xwayland
+    #[cfg(target_os = "linux")]
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        log::info!("use platform wayland");
+        std::sync::Arc::new(wayland::Wayland::new(app))
+    } else {
+        log::info!("use platform x11");
+        std::sync::Arc::new(x11::X11::new(app))
+    }
+}
+
+// keysym at https://gitlab.freedesktop.org/xorg/proto/xorgproto/-/blob/master/include/X11/keysymdef.h
+#[cfg(target_os = "linux")]
+mod keysym {
+    pub const XK_C: u32 = 0x0063;
+    pub const XK_V: u32 = 0x0076;
+    pub const XK_Z: u32 = 0x007a;
+    pub const XK_BACKSPACE: u32 = 0xff08;
+    pub const XK_TAB: u32 = 0xff09;
+    pub const XK_RETURN: u32 = 0xff0d;
+    pub const XK_INSERT: u32 = 0xff63;
+    pub const XK_SHIFT_L: u32 = 0xffe1;
+    pub const XK_SHIFT_R: u32 = 0xffe2;
+    pub const XK_CONTROL_L: u32 = 0xffe3;
+    pub const XK_CONTROL_R: u32 = 0xffe4;
+    pub const XK_ALT_L: u32 = 0xffe9;
+    pub const XK_ALT_R: u32 = 0xffea;
+    pub const XK_SUPER_L: u32 = 0xffeb;
+    pub const XK_SUPER_R: u32 = 0xffec;
+}
+#[cfg(target_os = "linux")]
+use keysym::*;
+
+// send_text skips control characters other than line break and tab
+// since keys such as escape run commands in the target
+pub fn is_typed(c: char) -> bool {
+    c == '\n' || c == '\t' || !c.is_control()
+}
+
+// keysym of the character, latin-1 is mapped directly and others by unicode keysym
+#[cfg(target_os = "linux")]
+fn char_keysym(c: char) -> Option<u32> {
+    match c {
+        '\n' => Some(XK_RETURN),
+        '\t' => Some(XK_TAB),
+        c if !is_typed(c) => None,
+        ' '..='~' | '\u{a0}'..='\u{ff}' => Some(c as u32),
+        _ => Some(0x0100_0000 | c as u32),
+    }
+}
+
+// primary selection of x11, also shared with xwayland
+#[cfg(target_os = "linux")]
+fn read_arboard_primary_selection() -> anyhow::Result<Option<String>> {
+    use arboard::GetExtLinux;
+    let text = arboard::Clipboard::new()?
+        .get()
+        .clipboard(arboard::LinuxClipboardKind::Primary)
+        .text()?;
+    Ok(Some(text))
+}
+
+// clipboard of native platforms through the clipboard plugin
+fn read_plugin_clipboard(app: &tauri::AppHandle) -> anyhow::Result<ClipboardContent> {
+    let clipboard = tauri_plugin_clipboard_manager::ClipboardExt::clipboard(app);
+    if let Ok(text) = clipboard.read_text() {
+        return Ok(ClipboardContent::Text(text));
+    }
+    if let Ok(image) = clipboard.read_image() {
+        return Ok(ClipboardContent::Image(image.to_owned()));
+    }
+    Ok(ClipboardContent::Empty)
+}
+
+fn write_plugin_clipboard(
+    app: &tauri::AppHandle,
+    content: &ClipboardContent,
+) -> anyhow::Result<()> {
+    let clipboard = tauri_plugin_clipboard_manager::ClipboardExt::clipboard(app);
+    match content {
+        ClipboardContent::Text(text) => clipboard.write_text(text.as_str())?,
+        ClipboardContent::Image(image) => clipboard.write_image(image)?,
+        ClipboardContent::Empty => clipboard.clear()?,
+    }
+    Ok(())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keysym_latin() {
+        assert_eq!(char_keysym('a'), Some(0x61));
+        assert_eq!(char_keysym('~'), Some(0x7e));
+        assert_eq!(char_keysym('é'), Some(0xe9));
+    }
+
+    #[test]
+    fn keysym_unicode() {
+        assert_eq!(char_keysym('あ'), Some(0x0100_3042));
+        assert_eq!(char_keysym('😀'), Some(0x0101_f600));
+    }
+
+    #[test]
+    fn keysym_control() {
+        assert_eq!(char_keysym('\n'), Some(XK_RETURN));
+        assert_eq!(char_keysym('\t'), Some(XK_TAB));
+        assert_eq!(char_keysym('\u{1b}'), None);
+        assert_eq!(char_keysym('\u{7f}'), None);
+    }
+}
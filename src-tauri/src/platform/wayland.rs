@@ -0,0 +1,415 @@
+use std::io::Write;
+use std::os::fd::AsFd;
+
+use anyhow::Context;
+use wayland_client::protocol::{wl_registry, wl_seat};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::{
+    zwp_virtual_keyboard_manager_v1, zwp_virtual_keyboard_v1,
+};
+
+use super::*;
+
+// modifier mask of the "complete" xkb compatibility
+const MOD_SHIFT: u32 = 1;
+const MOD_CONTROL: u32 = 1 << 2;
+
+// restore token lets the portal skip the permission dialog after the first time
+static RESTORE_TOKEN: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+
+pub struct Wayland {
+    app: tauri::AppHandle,
+}
+
+impl Wayland {
+    pub fn new(app: &tauri::AppHandle) -> Self {
+        let app = app.clone();
+        Self { app }
+    }
+}
+
+// wayland has no protocol to query or activate windows of other clients
+impl Platform for Wayland {
+    fn get_foreground_window(&self) -> anyhow::Result<WindowId> {
+        Err(anyhow::anyhow!(
+            "foreground window is not available on wayland"
+        ))
+    }
+
+    fn is_own_window(&self, _: WindowId) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    fn get_window_rect(&self, _: WindowId) -> anyhow::Result<Rect> {
+        Err(anyhow::anyhow!("window rect is not available on wayland"))
+    }
+
+    fn get_caret_rect(&self, _: WindowId) -> anyhow::Result<Rect> {
+        Err(anyhow::anyhow!("caret is not available on wayland"))
+    }
+
+    fn get_work_area(&self, monitor: Rect) -> anyhow::Result<Rect> {
+        Ok(monitor)
+    }
+
+    fn get_app_info(&self, _: WindowId) -> anyhow::Result<AppInfo> {
+        Err(anyhow::anyhow!("app info is not available on wayland"))
+    }
+
+    fn focus_window(&self, _: WindowId) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("focus window is not available on wayland"))
+    }
+
+    fn read_clipboard(&self) -> anyhow::Result<ClipboardContent> {
+        read_plugin_clipboard(&self.app)
+    }
+
+    fn write_clipboard(&self, content: &ClipboardContent) -> anyhow::Result<()> {
+        write_plugin_clipboard(&self.app, content)
+    }
+
+    // primary selection is shared with xwayland
+    fn read_primary_selection(&self) -> anyhow::Result<Option<String>> {
+        read_arboard_primary_selection()
+    }
+
+    fn send_paste(&self, key: PasteKey) -> anyhow::Result<()> {
+        match key {
+            PasteKey::CtrlV => send_chord(&[XK_CONTROL_L], MOD_CONTROL, XK_V),
+            PasteKey::CtrlShiftV => {
+                send_chord(&[XK_CONTROL_L, XK_SHIFT_L], MOD_CONTROL | MOD_SHIFT, XK_V)
+            }
+            PasteKey::ShiftInsert => send_chord(&[XK_SHIFT_L], MOD_SHIFT, XK_INSERT),
+        }
+    }
+
+    fn send_copy(&self) -> anyhow::Result<()> {
+        send_chord(&[XK_CONTROL_L], MOD_CONTROL, XK_C)
+    }
+
+    fn send_undo(&self) -> anyhow::Result<()> {
+        send_chord(&[XK_CONTROL_L], MOD_CONTROL, XK_Z)
+    }
+
+    fn send_backspace(&self, count: usize) -> anyhow::Result<()> {
+        send_keysyms(&vec![XK_BACKSPACE; count], Default::default())
+    }
+
+    fn send_text(&self, text: &str, interval: std::time::Duration) -> anyhow::Result<()> {
+        let keysyms = text.chars().filter_map(char_keysym).collect::<Vec<_>>();
+        send_keysyms(&keysyms, interval)
+    }
+}
+
+fn send_keysyms(keysyms: &[u32], interval: std::time::Duration) -> anyhow::Result<()> {
+    match send_keysyms_virtual_keyboard(keysyms, interval) {
+        Ok(_) => return Ok(()),
+        Err(e) => log::info!("fallback to remote desktop portal: {}", e),
+    }
+
+    let events = keysyms
+        .iter()
+        .flat_map(|keysym| [(*keysym, true), (*keysym, false)])
+        .collect::<Vec<_>>();
+    send_keysyms_portal(events, interval)
+}
+
+// virtual keyboard is supported by wlroots and kwin, the portal by gnome and kde
+fn send_chord(modifiers: &[u32], modifier_mask: u32, key: u32) -> anyhow::Result<()> {
+    match send_chord_virtual_keyboard(modifier_mask, key) {
+        Ok(_) => return Ok(()),
+        Err(e) => log::info!("fallback to remote desktop portal: {}", e),
+    }
+
+    let mut events = vec![];
+    events.extend(modifiers.iter().map(|modifier| (*modifier, true)));
+    events.push((key, true));
+    events.push((key, false));
+    events.extend(modifiers.iter().rev().map(|modifier| (*modifier, false)));
+    send_keysyms_portal(events, Default::default())
+}
+
+#[derive(Default)]
+struct VirtualKeyboardState {
+    seat: Option<wl_seat::WlSeat>,
+    manager: Option<zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1>,
+}
+
+impl wayland_client::Dispatch<wl_registry::WlRegistry, ()> for VirtualKeyboardState {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &wayland_client::Connection,
+        qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name, interface, ..
+        } = event
+        {
+            match interface.as_str() {
+                "wl_seat" if state.seat.is_none() => {
+                    state.seat = Some(registry.bind(name, 1, qh, ()));
+                }
+                "zwp_virtual_keyboard_manager_v1" => {
+                    state.manager = Some(registry.bind(name, 1, qh, ()));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+wayland_client::delegate_noop!(VirtualKeyboardState: ignore wl_seat::WlSeat);
+wayland_client::delegate_noop!(
+    VirtualKeyboardState: zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1
+);
+wayland_client::delegate_noop!(VirtualKeyboardState: zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1);
+
+// virtual keyboard at https://gitlab.freedesktop.org/wlroots/wlr-protocols/-/blob/master/unstable/virtual-keyboard-unstable-v1.xml
+struct VirtualKeyboard {
+    queue: wayland_client::EventQueue<VirtualKeyboardState>,
+    state: VirtualKeyboardState,
+    keyboard: zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1,
+}
+
+impl VirtualKeyboard {
+    fn connect() -> anyhow::Result<Self> {
+        let conn = wayland_client::Connection::connect_to_env()?;
+        let mut queue = conn.new_event_queue();
+        let qh = queue.handle();
+
+        let mut state = VirtualKeyboardState::default();
+        conn.display().get_registry(&qh, ());
+        queue.roundtrip(&mut state)?;
+
+        let seat = state.seat.as_ref().context("seat is not found")?;
+        let manager = state
+            .manager
+            .as_ref()
+            .context("virtual keyboard is not supported")?;
+        let keyboard = manager.create_virtual_keyboard(seat, &qh, ());
+
+        Ok(Self {
+            queue,
+            state,
+            keyboard,
+        })
+    }
+
+    // key of index in keysyms is sent by keycode index + 1
+    fn keymap(&mut self, keysyms: &[u32]) -> anyhow::Result<()> {
+        let keymap = create_keymap(keysyms);
+        let file = create_keymap_file(&keymap)?;
+        const KEYMAP_FORMAT_XKB_V1: u32 = 1;
+        self.keyboard
+            .keymap(KEYMAP_FORMAT_XKB_V1, file.as_fd(), keymap.len() as u32 + 1);
+        self.queue.roundtrip(&mut self.state)?;
+        Ok(())
+    }
+
+    fn modifiers(&mut self, modifier_mask: u32) {
+        self.keyboard.modifiers(modifier_mask, 0, 0, 0);
+    }
+
+    fn tap(&mut self, index: usize) -> anyhow::Result<()> {
+        const KEY_RELEASED: u32 = 0;
+        const KEY_PRESSED: u32 = 1;
+        let time = timestamp();
+        self.keyboard.key(time, index as u32 + 1, KEY_PRESSED);
+        self.keyboard.key(time, index as u32 + 1, KEY_RELEASED);
+        self.queue.roundtrip(&mut self.state)?;
+        Ok(())
+    }
+
+    fn destroy(mut self) -> anyhow::Result<()> {
+        self.keyboard.destroy();
+        self.queue.roundtrip(&mut self.state)?;
+        Ok(())
+    }
+}
+
+fn send_chord_virtual_keyboard(modifier_mask: u32, key: u32) -> anyhow::Result<()> {
+    let mut keyboard = VirtualKeyboard::connect()?;
+
+    // keymap with the single key, so the key is always the first keycode
+    keyboard.keymap(&[key])?;
+    keyboard.modifiers(modifier_mask);
+    keyboard.tap(0)?;
+    keyboard.modifiers(0);
+
+    keyboard.destroy()
+}
+
+fn send_keysyms_virtual_keyboard(
+    keysyms: &[u32],
+    interval: std::time::Duration,
+) -> anyhow::Result<()> {
+    let mut keyboard = VirtualKeyboard::connect()?;
+
+    // switch keymap per chunk to keep keycodes within the range of x11 clients
+    const KEYMAP_SIZE: usize = 200;
+    for chunk in keysyms.chunks(KEYMAP_SIZE) {
+        keyboard.keymap(chunk)?;
+        for index in 0..chunk.len() {
+            keyboard.tap(index)?;
+            std::thread::sleep(interval);
+        }
+    }
+
+    keyboard.destroy()
+}
+
+// keycodes start at 9 so that evdev keycode (xkb keycode - 8) starts at 1
+fn create_keymap(keysyms: &[u32]) -> String {
+    let mut keycodes = String::new();
+    let mut symbols = String::new();
+    for (index, keysym) in keysyms.iter().enumerate() {
+        keycodes.push_str(&format!("<K{}> = {};\n", index, index + 9));
+        symbols.push_str(&format!("key <K{}> {{ [ {:#x} ] }};\n", index, keysym));
+    }
+
+    format!(
+        "xkb_keymap {{\n\
+         xkb_keycodes \"quicklime\" {{\nminimum = 8;\nmaximum = {};\n{}}};\n\
+         xkb_types \"quicklime\" {{ include \"complete\" }};\n\
+         xkb_compatibility \"quicklime\" {{ include \"complete\" }};\n\
+         xkb_symbols \"quicklime\" {{\n{}}};\n\
+         }};\n",
+        keysyms.len() + 9,
+        keycodes,
+        symbols
+    )
+}
+
+// keymap is passed by file descriptor, so write it to an unlinked file
+fn create_keymap_file(keymap: &str) -> anyhow::Result<std::fs::File> {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let path = dir.join(format!("quicklime-keymap-{}", std::process::id()));
+
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)?;
+    std::fs::remove_file(&path)?;
+
+    file.write_all(keymap.as_bytes())?;
+    file.write_all(&[0])?;
+    file.flush()?;
+
+    Ok(file)
+}
+
+fn timestamp() -> u32 {
+    let duration = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    duration.as_millis() as u32
+}
+
+// run on another thread because the caller may be inside async runtime
+fn send_keysyms_portal(
+    events: Vec<(u32, bool)>,
+    interval: std::time::Duration,
+) -> anyhow::Result<()> {
+    std::thread::spawn(move || {
+        tauri::async_runtime::block_on(send_keysyms_portal_inner(events, interval))
+    })
+    .join()
+    .map_err(|_| anyhow::anyhow!("remote desktop portal thread panicked"))?
+}
+
+// RemoteDesktop portal at https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.RemoteDesktop.html
+async fn send_keysyms_portal_inner(
+    events: Vec<(u32, bool)>,
+    interval: std::time::Duration,
+) -> anyhow::Result<()> {
+    use ashpd::desktop::remote_desktop::*;
+
+    let proxy = RemoteDesktop::new().await?;
+    let session = proxy.create_session().await?;
+
+    let restore_token = RESTORE_TOKEN.lock().ok().and_then(|token| token.clone());
+    proxy
+        .select_devices(
+            &session,
+            DeviceType::Keyboard.into(),
+            restore_token.as_deref(),
+            ashpd::desktop::PersistMode::ExplicitlyRevoked,
+        )
+        .await?;
+    let devices = proxy.start(&session, None).await?.response()?;
+    if let Ok(mut token) = RESTORE_TOKEN.lock() {
+        *token = devices.restore_token().map(Into::into);
+    }
+
+    for (keysym, pressed) in events {
+        let state = if pressed {
+            KeyState::Pressed
+        } else {
+            KeyState::Released
+        };
+        proxy
+            .notify_keyboard_keysym(&session, keysym as i32, state)
+            .await?;
+        if !pressed {
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    session.close().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keymap_keycodes() {
+        let keymap = create_keymap(&[XK_V, 0x0100_3042]);
+        assert!(keymap.contains("maximum = 11;"));
+        assert!(keymap.contains("<K0> = 9;"));
+        assert!(keymap.contains("<K1> = 10;"));
+        assert!(keymap.contains("key <K0> { [ 0x76 ] };"));
+        assert!(keymap.contains("key <K1> { [ 0x1003042 ] };"));
+    }
+
+    #[test]
+    fn keymap_file() {
+        let keymap = create_keymap(&[XK_V]);
+        let mut file = create_keymap_file(&keymap).unwrap();
+        let mut content = vec![];
+        std::io::Seek::rewind(&mut file).unwrap();
+        std::io::Read::read_to_end(&mut file, &mut content).unwrap();
+        assert_eq!(content.len(), keymap.len() + 1);
+        assert_eq!(content.last(), Some(&0));
+    }
+
+    // needs a wlroots compositor, run by scripts/test-sway.sh with headless sway
+    #[test]
+    #[ignore]
+    fn sway_virtual_keyboard() {
+        assert!(std::env::var_os("WAYLAND_DISPLAY").is_some());
+        send_chord_virtual_keyboard(MOD_CONTROL, XK_C).unwrap();
+        let keysyms = "a\tあ\n"
+            .chars()
+            .filter_map(char_keysym)
+            .collect::<Vec<_>>();
+        send_keysyms_virtual_keyboard(&keysyms, Default::default()).unwrap();
+    }
+
+    // keymap is switched per chunk, so text longer than a keymap is sent in pieces
+    #[test]
+    #[ignore]
+    fn sway_virtual_keyboard_long_text() {
+        assert!(std::env::var_os("WAYLAND_DISPLAY").is_some());
+        let keysyms = vec![XK_BACKSPACE; 450];
+        send_keysyms_virtual_keyboard(&keysyms, Default::default()).unwrap();
+    }
+}
@@ -0,0 +1,307 @@
+use anyhow::Context;
+
+use super::*;
+
+const EXTRA_INFO: usize = 0x5555;
+
+fn hwnd(window: WindowId) -> windows::Win32::Foundation::HWND {
+    windows::Win32::Foundation::HWND(window.0 as _)
+}
+
+pub struct Win32 {
+    app: tauri::AppHandle,
+}
+
+impl Win32 {
+    pub fn new(app: &tauri::AppHandle) -> Self {
+        let app = app.clone();
+        Self { app }
+    }
+}
+
+impl Platform for Win32 {
+    fn get_foreground_window(&self) -> anyhow::Result<WindowId> {
+        let hwnd = unsafe { windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow() };
+        if hwnd.is_invalid() {
+            return Err(anyhow::anyhow!("foreground window is not found"));
+        }
+        Ok(WindowId(hwnd.0 as u64))
+    }
+
+    fn is_own_window(&self, window: WindowId) -> anyhow::Result<bool> {
+        for webview_window in tauri::Manager::webview_windows(&self.app).values() {
+            if webview_window.hwnd()? == hwnd(window) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_window_rect(&self, window: WindowId) -> anyhow::Result<Rect> {
+        let mut lprect = windows::Win32::Foundation::RECT::default();
+        unsafe {
+            windows::Win32::UI::WindowsAndMessaging::GetWindowRect(hwnd(window), &mut lprect)
+        }
+        .context("failed to get window rect")?;
+
+        Ok(Rect {
+            left: lprect.left,
+            top: lprect.top,
+            right: lprect.right,
+            bottom: lprect.bottom,
+        })
+    }
+
+    // GetGUIThreadInfo at https://learn.microsoft.com/ja-jp/windows/win32/api/winuser/nf-winuser-getguithreadinfo
+    fn get_caret_rect(&self, window: WindowId) -> anyhow::Result<Rect> {
+        use windows::Win32::Graphics::Gdi::*;
+        use windows::Win32::UI::WindowsAndMessaging::*;
+
+        let thread = unsafe { GetWindowThreadProcessId(hwnd(window), None) };
+        let mut info = GUITHREADINFO {
+            cbSize: std::mem::size_of::<GUITHREADINFO>() as u32,
+            ..Default::default()
+        };
+        unsafe { GetGUIThreadInfo(thread, &mut info) }.context("failed to get gui thread info")?;
+        if info.hwndCaret.is_invalid() {
+            return Err(anyhow::anyhow!("caret is not found"));
+        }
+
+        // caret rect is relative to the client area of the caret window
+        let mut points = [
+            windows::Win32::Foundation::POINT {
+                x: info.rcCaret.left,
+                y: info.rcCaret.top,
+            },
+            windows::Win32::Foundation::POINT {
+                x: info.rcCaret.right,
+                y: info.rcCaret.bottom,
+            },
+        ];
+        for point in &mut points {
+            unsafe { ClientToScreen(info.hwndCaret, point) }
+                .ok()
+                .context("failed to convert caret position")?;
+        }
+
+        Ok(Rect {
+            left: points[0].x,
+            top: points[0].y,
+            right: points[1].x,
+            bottom: points[1].y,
+        })
+    }
+
+    // GetMonitorInfo at https://learn.microsoft.com/ja-jp/windows/win32/api/winuser/nf-winuser-getmonitorinfow
+    fn get_work_area(&self, monitor: Rect) -> anyhow::Result<Rect> {
+        use windows::Win32::Graphics::Gdi::*;
+
+        let lprc = windows::Win32::Foundation::RECT {
+            left: monitor.left,
+            top: monitor.top,
+            right: monitor.right,
+            bottom: monitor.bottom,
+        };
+        let hmonitor = unsafe { MonitorFromRect(&lprc, MONITOR_DEFAULTTONEAREST) };
+        let mut info = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        unsafe { GetMonitorInfoW(hmonitor, &mut info) }
+            .ok()
+            .context("failed to get monitor info")?;
+
+        Ok(Rect {
+            left: info.rcWork.left,
+            top: info.rcWork.top,
+            right: info.rcWork.right,
+            bottom: info.rcWork.bottom,
+        })
+    }
+
+    // GetClassName at https://learn.microsoft.com/ja-jp/windows/win32/api/winuser/nf-winuser-getclassnamew
+    fn get_app_info(&self, window: WindowId) -> anyhow::Result<AppInfo> {
+        use windows::Win32::System::Threading::*;
+        use windows::Win32::UI::WindowsAndMessaging::*;
+
+        let hwnd = hwnd(window);
+
+        let mut class = [0u16; 256];
+        let len = unsafe { GetClassNameW(hwnd, &mut class) };
+        let class = String::from_utf16_lossy(&class[..len.max(0) as usize]);
+
+        let mut pid = 0;
+        unsafe { GetWindowThreadProcessId(hwnd, Some(&mut pid)) };
+        let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }
+            .context("failed to open process")?;
+        let mut name = [0u16; 1024];
+        let mut size = name.len() as u32;
+        let result = unsafe {
+            QueryFullProcessImageNameW(
+                handle,
+                PROCESS_NAME_WIN32,
+                windows::core::PWSTR(name.as_mut_ptr()),
+                &mut size,
+            )
+        };
+        let _ = unsafe { windows::Win32::Foundation::CloseHandle(handle) };
+        result.context("failed to query process name")?;
+
+        // compare by file stem, e.g. "slack" for "slack.exe"
+        let path = String::from_utf16_lossy(&name[..size as usize]);
+        let process = std::path::Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        Ok(AppInfo { process, class })
+    }
+
+    // SetForegroundWindow at https://learn.microsoft.com/ja-jp/windows/win32/api/winuser/nf-winuser-setforegroundwindow
+    fn focus_window(&self, window: WindowId) -> anyhow::Result<()> {
+        use windows::Win32::UI::WindowsAndMessaging::*;
+
+        let hwnd = hwnd(window);
+        if !unsafe { IsWindow(Some(hwnd)) }.as_bool() {
+            return Err(anyhow::anyhow!("window no longer exists"));
+        }
+        unsafe { SetForegroundWindow(hwnd) }
+            .ok()
+            .context("failed to set foreground window")?;
+        Ok(())
+    }
+
+    fn read_clipboard(&self) -> anyhow::Result<ClipboardContent> {
+        read_plugin_clipboard(&self.app)
+    }
+
+    fn write_clipboard(&self, content: &ClipboardContent) -> anyhow::Result<()> {
+        write_plugin_clipboard(&self.app, content)
+    }
+
+    fn read_primary_selection(&self) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn send_paste(&self, key: PasteKey) -> anyhow::Result<()> {
+        use windows::Win32::UI::Input::KeyboardAndMouse::*;
+        release_modifiers();
+        let (modifiers, key) = match key {
+            PasteKey::CtrlV => (&[VK_CONTROL][..], VK_V),
+            PasteKey::CtrlShiftV => (&[VK_CONTROL, VK_SHIFT][..], VK_V),
+            PasteKey::ShiftInsert => (&[VK_SHIFT][..], VK_INSERT),
+        };
+        for modifier in modifiers {
+            send_input(*modifier, Default::default());
+        }
+        send_input(key, Default::default());
+        send_input(key, KEYEVENTF_KEYUP);
+        for modifier in modifiers.iter().rev() {
+            send_input(*modifier, KEYEVENTF_KEYUP);
+        }
+        Ok(())
+    }
+
+    fn send_copy(&self) -> anyhow::Result<()> {
+        use windows::Win32::UI::Input::KeyboardAndMouse::*;
+        release_modifiers();
+        // send ctrl c
+        send_input(VK_CONTROL, Default::default());
+        send_input(VK_C, Default::default());
+        send_input(VK_C, KEYEVENTF_KEYUP);
+        send_input(VK_CONTROL, KEYEVENTF_KEYUP);
+        Ok(())
+    }
+
+    fn send_undo(&self) -> anyhow::Result<()> {
+        use windows::Win32::UI::Input::KeyboardAndMouse::*;
+        release_modifiers();
+        // send ctrl z
+        send_input(VK_CONTROL, Default::default());
+        send_input(VK_Z, Default::default());
+        send_input(VK_Z, KEYEVENTF_KEYUP);
+        send_input(VK_CONTROL, KEYEVENTF_KEYUP);
+        Ok(())
+    }
+
+    fn send_backspace(&self, count: usize) -> anyhow::Result<()> {
+        use windows::Win32::UI::Input::KeyboardAndMouse::*;
+        release_modifiers();
+        for _ in 0..count {
+            send_input(VK_BACK, Default::default());
+            send_input(VK_BACK, KEYEVENTF_KEYUP);
+        }
+        Ok(())
+    }
+
+    // KEYEVENTF_UNICODE types characters regardless of keyboard layout
+    fn send_text(&self, text: &str, interval: std::time::Duration) -> anyhow::Result<()> {
+        use windows::Win32::UI::Input::KeyboardAndMouse::*;
+        release_modifiers();
+        for c in text.chars() {
+            match c {
+                // line break as unicode is ignored by some controls
+                '\n' => {
+                    send_input(VK_RETURN, Default::default());
+                    send_input(VK_RETURN, KEYEVENTF_KEYUP);
+                }
+                c if !is_typed(c) => continue,
+                _ => {
+                    let mut units = [0u16; 2];
+                    for unit in c.encode_utf16(&mut units) {
+                        send_unicode(*unit, Default::default());
+                        send_unicode(*unit, KEYEVENTF_KEYUP);
+                    }
+                }
+            }
+            std::thread::sleep(interval);
+        }
+        Ok(())
+    }
+}
+
+// key up modifier keys
+fn release_modifiers() {
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+    send_input(VK_LCONTROL, KEYEVENTF_KEYUP);
+    send_input(VK_RCONTROL, KEYEVENTF_KEYUP);
+    send_input(VK_LWIN, KEYEVENTF_KEYUP);
+    send_input(VK_RWIN, KEYEVENTF_KEYUP);
+    send_input(VK_LSHIFT, KEYEVENTF_KEYUP);
+    send_input(VK_RSHIFT, KEYEVENTF_KEYUP);
+    send_input(VK_LMENU, KEYEVENTF_KEYUP);
+    send_input(VK_RMENU, KEYEVENTF_KEYUP);
+}
+
+// SendInput at https://learn.microsoft.com/ja-jp/windows/win32/api/winuser/nf-winuser-sendinput
+fn send_input(
+    key_code: windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY,
+    flags: windows::Win32::UI::Input::KeyboardAndMouse::KEYBD_EVENT_FLAGS,
+) {
+    let mut pinput = windows::Win32::UI::Input::KeyboardAndMouse::INPUT::default();
+    pinput.r#type = windows::Win32::UI::Input::KeyboardAndMouse::INPUT_KEYBOARD;
+    pinput.Anonymous.ki.wVk = key_code;
+    pinput.Anonymous.ki.dwFlags = flags;
+    pinput.Anonymous.ki.dwExtraInfo = EXTRA_INFO;
+    let cbsize = std::mem::size_of_val(&pinput) as i32;
+    unsafe { windows::Win32::UI::Input::KeyboardAndMouse::SendInput(&[pinput], cbsize) };
+}
+
+fn send_unicode(unit: u16, flags: windows::Win32::UI::Input::KeyboardAndMouse::KEYBD_EVENT_FLAGS) {
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+
+    let pinput = INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: VIRTUAL_KEY(0),
+                wScan: unit,
+                dwFlags: KEYEVENTF_UNICODE | flags,
+                time: 0,
+                dwExtraInfo: EXTRA_INFO,
+            },
+        },
+    };
+    let cbsize = std::mem::size_of_val(&pinput) as i32;
+    unsafe { SendInput(&[pinput], cbsize) };
+}
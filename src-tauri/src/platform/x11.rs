@@ -0,0 +1,545 @@
+use anyhow::Context;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::ConnectionExt as _;
+use x11rb::protocol::xtest::ConnectionExt as _;
+
+use super::*;
+
+struct X11Connection {
+    conn: x11rb::rust_connection::RustConnection,
+    root: x11rb::protocol::xproto::Window,
+}
+
+impl X11Connection {
+    fn connect() -> anyhow::Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        Ok(Self { conn, root })
+    }
+
+    fn atom(&self, name: &str) -> anyhow::Result<x11rb::protocol::xproto::Atom> {
+        let atom = self.conn.intern_atom(false, name.as_bytes())?.reply()?.atom;
+        Ok(atom)
+    }
+
+    fn get_property_u32(
+        &self,
+        window: x11rb::protocol::xproto::Window,
+        property: x11rb::protocol::xproto::Atom,
+        r#type: x11rb::protocol::xproto::AtomEnum,
+    ) -> anyhow::Result<Option<u32>> {
+        let value = self
+            .conn
+            .get_property(false, window, property, r#type, 0, 1)?
+            .reply()?
+            .value32()
+            .and_then(|mut value| value.next());
+        Ok(value)
+    }
+
+    fn keycode(&self, keysym: u32) -> anyhow::Result<u8> {
+        let setup = self.conn.setup();
+        let count = setup.max_keycode - setup.min_keycode + 1;
+        let mapping = self
+            .conn
+            .get_keyboard_mapping(setup.min_keycode, count)?
+            .reply()?;
+
+        let keysyms_per_keycode = mapping.keysyms_per_keycode.max(1) as usize;
+        let index = mapping
+            .keysyms
+            .chunks(keysyms_per_keycode)
+            .position(|keysyms| keysyms.contains(&keysym))
+            .with_context(|| format!("keysym {:#x} is not mapped", keysym))?;
+        Ok(setup.min_keycode + index as u8)
+    }
+
+    // XTest at https://www.x.org/releases/X11R7.7/doc/xextproto/xtest.html
+    fn send_key(&self, keysym: u32, press: bool) -> anyhow::Result<()> {
+        let r#type = if press {
+            x11rb::protocol::xproto::KEY_PRESS_EVENT
+        } else {
+            x11rb::protocol::xproto::KEY_RELEASE_EVENT
+        };
+        let keycode = self.keycode(keysym)?;
+        self.conn
+            .xtest_fake_input(r#type, keycode, 0, self.root, 0, 0, 0)?;
+        Ok(())
+    }
+
+    // key up modifier keys, ignore modifiers absent in keyboard mapping
+    fn release_modifiers(&self) -> anyhow::Result<()> {
+        for keysym in [
+            XK_CONTROL_L,
+            XK_CONTROL_R,
+            XK_SUPER_L,
+            XK_SUPER_R,
+            XK_SHIFT_L,
+            XK_SHIFT_R,
+            XK_ALT_L,
+            XK_ALT_R,
+        ] {
+            if let Ok(keycode) = self.keycode(keysym) {
+                let r#type = x11rb::protocol::xproto::KEY_RELEASE_EVENT;
+                self.conn
+                    .xtest_fake_input(r#type, keycode, 0, self.root, 0, 0, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn send_chord(&self, modifiers: &[u32], key: u32) -> anyhow::Result<()> {
+        self.release_modifiers()?;
+        for modifier in modifiers {
+            self.send_key(*modifier, true)?;
+        }
+        self.send_key(key, true)?;
+        self.send_key(key, false)?;
+        for modifier in modifiers.iter().rev() {
+            self.send_key(*modifier, false)?;
+        }
+        // round trip to wait for the fake input to be processed
+        self.conn.get_input_focus()?.reply()?;
+        Ok(())
+    }
+
+    fn send_repeat(&self, key: u32, count: usize) -> anyhow::Result<()> {
+        self.release_modifiers()?;
+        for _ in 0..count {
+            self.send_key(key, true)?;
+            self.send_key(key, false)?;
+        }
+        self.conn.get_input_focus()?.reply()?;
+        Ok(())
+    }
+
+    // remap a spare keycode to each character in turn, as xdotool does
+    fn send_text(&self, text: &str, interval: std::time::Duration) -> anyhow::Result<()> {
+        self.release_modifiers()?;
+
+        let setup = self.conn.setup();
+        let count = setup.max_keycode - setup.min_keycode + 1;
+        let mapping = self
+            .conn
+            .get_keyboard_mapping(setup.min_keycode, count)?
+            .reply()?;
+
+        // keycode without any keysym is free to remap
+        let keysyms_per_keycode = mapping.keysyms_per_keycode.max(1);
+        let index = mapping
+            .keysyms
+            .chunks(keysyms_per_keycode as usize)
+            .rposition(|keysyms| keysyms.iter().all(|keysym| *keysym == x11rb::NO_SYMBOL))
+            .context("spare keycode is not found")?;
+        let keycode = setup.min_keycode + index as u8;
+
+        let result = text.chars().filter_map(char_keysym).try_for_each(|keysym| {
+            let keysyms = vec![keysym; keysyms_per_keycode as usize];
+            self.conn
+                .change_keyboard_mapping(1, keycode, keysyms_per_keycode, &keysyms)?;
+            self.conn.get_input_focus()?.reply()?;
+
+            for r#type in [
+                x11rb::protocol::xproto::KEY_PRESS_EVENT,
+                x11rb::protocol::xproto::KEY_RELEASE_EVENT,
+            ] {
+                self.conn
+                    .xtest_fake_input(r#type, keycode, 0, self.root, 0, 0, 0)?;
+            }
+            self.conn.get_input_focus()?.reply()?;
+
+            std::thread::sleep(interval);
+            anyhow::Ok(())
+        });
+
+        // give the spare keycode back even if typing fails
+        let keysyms = vec![x11rb::NO_SYMBOL; keysyms_per_keycode as usize];
+        self.conn
+            .change_keyboard_mapping(1, keycode, keysyms_per_keycode, &keysyms)?;
+        self.conn.get_input_focus()?.reply()?;
+
+        result
+    }
+
+    // EWMH at https://specifications.freedesktop.org/wm-spec/latest/
+    fn active_window(&self) -> anyhow::Result<WindowId> {
+        let net_active_window = self.atom("_NET_ACTIVE_WINDOW")?;
+        let window = self
+            .get_property_u32(
+                self.root,
+                net_active_window,
+                x11rb::protocol::xproto::AtomEnum::WINDOW,
+            )?
+            .filter(|window| *window != x11rb::NONE)
+            .context("foreground window is not found")?;
+        Ok(WindowId(window as u64))
+    }
+
+    fn window_pid(&self, window: WindowId) -> anyhow::Result<Option<u32>> {
+        let net_wm_pid = self.atom("_NET_WM_PID")?;
+        self.get_property_u32(
+            window.0 as u32,
+            net_wm_pid,
+            x11rb::protocol::xproto::AtomEnum::CARDINAL,
+        )
+    }
+
+    fn window_rect(&self, window: WindowId) -> anyhow::Result<Rect> {
+        let window = window.0 as u32;
+
+        // geometry is relative to parent, so translate origin to root
+        let geometry = self.conn.get_geometry(window)?.reply()?;
+        let origin = self
+            .conn
+            .translate_coordinates(window, self.root, 0, 0)?
+            .reply()?;
+
+        let left = origin.dst_x as i32;
+        let top = origin.dst_y as i32;
+        Ok(Rect {
+            left,
+            top,
+            right: left + geometry.width as i32,
+            bottom: top + geometry.height as i32,
+        })
+    }
+
+    // _NET_WORKAREA spans all monitors, so intersect it with the monitor
+    fn work_area(&self, monitor: Rect) -> anyhow::Result<Rect> {
+        let net_workarea = self.atom("_NET_WORKAREA")?;
+        let workarea = self
+            .conn
+            .get_property(
+                false,
+                self.root,
+                net_workarea,
+                x11rb::protocol::xproto::AtomEnum::CARDINAL,
+                0,
+                4,
+            )?
+            .reply()?
+            .value32()
+            .map(|value| value.collect::<Vec<_>>())
+            .filter(|value| value.len() == 4)
+            .context("work area is not found")?;
+
+        let left = workarea[0] as i32;
+        let top = workarea[1] as i32;
+        Ok(Rect {
+            left: monitor.left.max(left),
+            top: monitor.top.max(top),
+            right: monitor.right.min(left + workarea[2] as i32),
+            bottom: monitor.bottom.min(top + workarea[3] as i32),
+        })
+    }
+
+    fn app_info(&self, window: WindowId) -> anyhow::Result<AppInfo> {
+        // WM_CLASS consists of null-terminated instance name and class name
+        let wm_class = self
+            .conn
+            .get_property(
+                false,
+                window.0 as u32,
+                x11rb::protocol::xproto::AtomEnum::WM_CLASS,
+                x11rb::protocol::xproto::AtomEnum::STRING,
+                0,
+                1024,
+            )?
+            .reply()?;
+        let class = wm_class
+            .value
+            .split(|byte| *byte == 0)
+            .nth(1)
+            .map(|class| String::from_utf8_lossy(class).into_owned())
+            .unwrap_or_default();
+
+        let process = self
+            .window_pid(window)?
+            .and_then(|pid| std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok())
+            .map(|comm| comm.trim().into())
+            .unwrap_or_default();
+
+        Ok(AppInfo { process, class })
+    }
+
+    // request window manager to activate the window as a pager does
+    fn activate(&self, window: WindowId) -> anyhow::Result<()> {
+        let window = window.0 as u32;
+
+        self.conn
+            .get_geometry(window)?
+            .reply()
+            .context("window no longer exists")?;
+
+        const SOURCE_PAGER: u32 = 2;
+        let net_active_window = self.atom("_NET_ACTIVE_WINDOW")?;
+        let event = x11rb::protocol::xproto::ClientMessageEvent::new(
+            32,
+            window,
+            net_active_window,
+            [SOURCE_PAGER, x11rb::CURRENT_TIME, 0, 0, 0],
+        );
+        let mask = x11rb::protocol::xproto::EventMask::SUBSTRUCTURE_REDIRECT
+            | x11rb::protocol::xproto::EventMask::SUBSTRUCTURE_NOTIFY;
+        self.conn.send_event(false, self.root, mask, event)?;
+        self.conn.get_input_focus()?.reply()?;
+        Ok(())
+    }
+}
+
+pub struct X11 {
+    app: tauri::AppHandle,
+}
+
+impl X11 {
+    pub fn new(app: &tauri::AppHandle) -> Self {
+        let app = app.clone();
+        Self { app }
+    }
+}
+
+impl Platform for X11 {
+    fn get_foreground_window(&self) -> anyhow::Result<WindowId> {
+        X11Connection::connect()?.active_window()
+    }
+
+    // own windows share the process id of this app
+    fn is_own_window(&self, window: WindowId) -> anyhow::Result<bool> {
+        let pid = X11Connection::connect()?.window_pid(window)?;
+        Ok(pid == Some(std::process::id()))
+    }
+
+    fn get_window_rect(&self, window: WindowId) -> anyhow::Result<Rect> {
+        X11Connection::connect()?.window_rect(window)
+    }
+
+    // no standard protocol exposes the caret, accessibility bus is out of scope
+    fn get_caret_rect(&self, _: WindowId) -> anyhow::Result<Rect> {
+        Err(anyhow::anyhow!("caret is not available on x11"))
+    }
+
+    fn get_work_area(&self, monitor: Rect) -> anyhow::Result<Rect> {
+        X11Connection::connect()?.work_area(monitor)
+    }
+
+    fn get_app_info(&self, window: WindowId) -> anyhow::Result<AppInfo> {
+        X11Connection::connect()?.app_info(window)
+    }
+
+    fn focus_window(&self, window: WindowId) -> anyhow::Result<()> {
+        X11Connection::connect()?.activate(window)
+    }
+
+    fn read_clipboard(&self) -> anyhow::Result<ClipboardContent> {
+        read_plugin_clipboard(&self.app)
+    }
+
+    fn write_clipboard(&self, content: &ClipboardContent) -> anyhow::Result<()> {
+        write_plugin_clipboard(&self.app, content)
+    }
+
+    fn read_primary_selection(&self) -> anyhow::Result<Option<String>> {
+        read_arboard_primary_selection()
+    }
+
+    fn send_paste(&self, key: PasteKey) -> anyhow::Result<()> {
+        let x11 = X11Connection::connect()?;
+        match key {
+            PasteKey::CtrlV => x11.send_chord(&[XK_CONTROL_L], XK_V),
+            PasteKey::CtrlShiftV => x11.send_chord(&[XK_CONTROL_L, XK_SHIFT_L], XK_V),
+            PasteKey::ShiftInsert => x11.send_chord(&[XK_SHIFT_L], XK_INSERT),
+        }
+    }
+
+    fn send_copy(&self) -> anyhow::Result<()> {
+        let x11 = X11Connection::connect()?;
+        x11.send_chord(&[XK_CONTROL_L], XK_C)
+    }
+
+    fn send_undo(&self) -> anyhow::Result<()> {
+        let x11 = X11Connection::connect()?;
+        x11.send_chord(&[XK_CONTROL_L], XK_Z)
+    }
+
+    fn send_backspace(&self, count: usize) -> anyhow::Result<()> {
+        let x11 = X11Connection::connect()?;
+        x11.send_repeat(XK_BACKSPACE, count)
+    }
+
+    fn send_text(&self, text: &str, interval: std::time::Duration) -> anyhow::Result<()> {
+        let x11 = X11Connection::connect()?;
+        x11.send_text(text, interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::protocol::xproto::ConnectionExt as _;
+    use x11rb::wrapper::ConnectionExt as _;
+
+    use super::*;
+
+    const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+    // client window standing in for the target application
+    struct TestWindow {
+        conn: x11rb::rust_connection::RustConnection,
+        window: x11rb::protocol::xproto::Window,
+    }
+
+    impl TestWindow {
+        fn open() -> Self {
+            let (conn, screen_num) = x11rb::connect(None).unwrap();
+            let screen = &conn.setup().roots[screen_num];
+            let window = conn.generate_id().unwrap();
+            let values = x11rb::protocol::xproto::CreateWindowAux::new()
+                .event_mask(x11rb::protocol::xproto::EventMask::KEY_PRESS);
+            conn.create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                screen.root,
+                0,
+                0,
+                320,
+                240,
+                0,
+                x11rb::protocol::xproto::WindowClass::INPUT_OUTPUT,
+                screen.root_visual,
+                &values,
+            )
+            .unwrap();
+            conn.change_property8(
+                x11rb::protocol::xproto::PropMode::REPLACE,
+                window,
+                x11rb::protocol::xproto::AtomEnum::WM_CLASS,
+                x11rb::protocol::xproto::AtomEnum::STRING,
+                b"editor\0QuicklimeEditor\0",
+            )
+            .unwrap();
+            let net_wm_pid = conn.intern_atom(false, b"_NET_WM_PID").unwrap();
+            conn.change_property32(
+                x11rb::protocol::xproto::PropMode::REPLACE,
+                window,
+                net_wm_pid.reply().unwrap().atom,
+                x11rb::protocol::xproto::AtomEnum::CARDINAL,
+                &[std::process::id()],
+            )
+            .unwrap();
+            conn.map_window(window).unwrap();
+            conn.sync().unwrap();
+            Self { conn, window }
+        }
+
+        fn id(&self) -> WindowId {
+            WindowId(self.window as u64)
+        }
+
+        // window manager maps and activates the window asynchronously
+        fn activate(&self, x11: &X11Connection) {
+            let instant = std::time::Instant::now();
+            while x11.active_window().ok() != Some(self.id()) {
+                assert!(instant.elapsed() < TIMEOUT, "window is not activated");
+                let _ = x11.activate(self.id());
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+        }
+
+        // key presses received until no event comes for a while
+        fn key_presses(&self) -> Vec<x11rb::protocol::xproto::KeyPressEvent> {
+            let mut events = vec![];
+            let mut instant = std::time::Instant::now();
+            while instant.elapsed() < std::time::Duration::from_millis(300) {
+                match self.conn.poll_for_event().unwrap() {
+                    Some(x11rb::protocol::Event::KeyPress(event)) => {
+                        events.push(event);
+                        instant = std::time::Instant::now();
+                    }
+                    Some(_) => {}
+                    None => std::thread::sleep(std::time::Duration::from_millis(10)),
+                }
+            }
+            events
+        }
+    }
+
+    // the tests below need an x server with a window manager, run by scripts/test-xvfb.sh
+    #[test]
+    #[ignore]
+    fn xvfb_foreground_profile() {
+        let x11 = X11Connection::connect().unwrap();
+        let window = TestWindow::open();
+        window.activate(&x11);
+
+        assert_eq!(x11.active_window().unwrap(), window.id());
+        assert_eq!(
+            x11.window_pid(window.id()).unwrap(),
+            Some(std::process::id())
+        );
+
+        let info = x11.app_info(window.id()).unwrap();
+        assert_eq!(info.class, "QuicklimeEditor");
+        assert!(!info.process.is_empty());
+
+        let profile = crate::app::Profile {
+            app: "quicklimeeditor".into(),
+            ..Default::default()
+        };
+        assert!(profile.is_match(&info));
+
+        let rect = x11.window_rect(window.id()).unwrap();
+        assert_eq!(rect.right - rect.left, 320);
+        assert_eq!(rect.bottom - rect.top, 240);
+    }
+
+    #[test]
+    #[ignore]
+    fn xvfb_paste() {
+        let x11 = X11Connection::connect().unwrap();
+        let window = TestWindow::open();
+        window.activate(&x11);
+
+        x11.send_chord(&[XK_CONTROL_L], XK_V).unwrap();
+        let keycode = x11.keycode(XK_V).unwrap();
+        let paste = window
+            .key_presses()
+            .into_iter()
+            .find(|event| event.detail == keycode)
+            .expect("paste key is not received");
+        assert!(paste
+            .state
+            .contains(x11rb::protocol::xproto::KeyButMask::CONTROL));
+    }
+
+    #[test]
+    #[ignore]
+    fn xvfb_type() {
+        let x11 = X11Connection::connect().unwrap();
+        let window = TestWindow::open();
+        window.activate(&x11);
+
+        // escape is skipped, other characters are typed one key each
+        x11.send_text("aé\u{1b}あ\n", Default::default()).unwrap();
+        assert_eq!(window.key_presses().len(), 4);
+
+        x11.send_repeat(XK_BACKSPACE, 3).unwrap();
+        let keycode = x11.keycode(XK_BACKSPACE).unwrap();
+        let presses = window.key_presses();
+        assert_eq!(presses.len(), 3);
+        assert!(presses.iter().all(|event| event.detail == keycode));
+    }
+
+    #[test]
+    #[ignore]
+    fn xvfb_selection() {
+        use arboard::SetExtLinux;
+        let mut clipboard = arboard::Clipboard::new().unwrap();
+        clipboard
+            .set()
+            .clipboard(arboard::LinuxClipboardKind::Primary)
+            .text("selected text")
+            .unwrap();
+
+        let text = read_arboard_primary_selection().unwrap();
+        assert_eq!(text.as_deref(), Some("selected text"));
+    }
+}